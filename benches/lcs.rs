@@ -0,0 +1,136 @@
+//! Peak memory and time of `lcs_core` for growing inputs.
+//!
+//! Run with `cargo bench --bench lcs`. Peak memory should grow linearly with the
+//! number of rows, while a full length table would grow with its square.
+//! Near-identical inputs should take milliseconds even at a million rows, inputs with more
+//! scattered changes than Myers' trace holds well under a second at 200k rows, and
+//! every strategy should stay near-linear on alternating unchanged and changed rows.
+
+use diff2d::lcs::{lcs_core, Aligner, Strategy};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+/// Counts live and peak heap bytes.
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(current, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// `rows` distinct values, and a copy with every 100th value replaced
+/// and every 150th value dropped.
+fn inputs(rows: usize) -> (Vec<u64>, Vec<u64>) {
+    let before: Vec<u64> = (0..rows as u64).collect();
+    let after = before
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| i % 150 != 149)
+        .map(|(i, &value)| {
+            if i % 100 == 99 {
+                value + rows as u64
+            } else {
+                value
+            }
+        })
+        .collect();
+    (before, after)
+}
+
+/// Peak heap bytes and milliseconds spent aligning `before` with `after`.
+fn measure(before: &[u64], after: &[u64]) -> (usize, f64) {
+    let baseline = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(baseline, Ordering::Relaxed);
+
+    let start = Instant::now();
+    let (map_to_before, _) = lcs_core(before, before.len(), after, after.len());
+    let elapsed = start.elapsed();
+
+    assert!(map_to_before.len() >= before.len());
+    (
+        PEAK.load(Ordering::Relaxed) - baseline,
+        elapsed.as_secs_f64() * 1000.0,
+    )
+}
+
+fn main() {
+    println!(
+        "{:>8} {:>12} {:>14} {:>16} {:>10}",
+        "rows", "peak bytes", "bytes per row", "full table bytes", "ms"
+    );
+    for rows in [1_000, 2_000, 4_000, 8_000, 16_000] {
+        let (before, after) = inputs(rows);
+        let (peak, ms) = measure(&before, &after);
+        let full_table = (before.len() + 1) * (after.len() + 1) * std::mem::size_of::<usize>();
+        println!(
+            "{:>8} {:>12} {:>14} {:>16} {:>10.1}",
+            rows,
+            peak,
+            peak / rows,
+            full_table,
+            ms
+        );
+    }
+
+    println!();
+    println!(
+        "{:>8} {:>8} {:>12} {:>10}",
+        "rows", "changes", "peak bytes", "ms"
+    );
+    let rows = 1_000_000;
+    let before: Vec<u64> = (0..rows).collect();
+    for changes in [0, 10, 100, 1_000] {
+        let mut after = before.clone();
+        for change in 0..changes {
+            after[(change * 7919 % rows) as usize] += rows;
+        }
+        let (peak, ms) = measure(&before, &after);
+        println!("{:>8} {:>8} {:>12} {:>10.1}", rows, changes, peak, ms);
+    }
+
+    println!();
+    println!(
+        "{:>8} {:>8} {:>12} {:>10}",
+        "rows", "changes", "peak bytes", "ms"
+    );
+    for rows in [10_000, 100_000, 200_000] {
+        // every 10th row changed, far beyond the edits Myers' trace may hold
+        let before: Vec<u64> = (0..rows).collect();
+        let after: Vec<u64> = (0..rows)
+            .map(|i| if i % 10 == 9 { i + rows } else { i })
+            .collect();
+        let (peak, ms) = measure(&before, &after);
+        println!("{:>8} {:>8} {:>12} {:>10.1}", rows, rows / 10, peak, ms);
+    }
+
+    println!();
+    println!("{:>10} {:>8} {:>10}", "strategy", "rows", "ms");
+    for strategy in [Strategy::Patience, Strategy::Histogram] {
+        for rows in [1_000, 10_000, 100_000] {
+            // every other row is changed
+            let before: Vec<u64> = (0..rows).collect();
+            let after: Vec<u64> = (0..rows)
+                .map(|i| if i % 2 == 1 { i + rows } else { i })
+                .collect();
+            let start = Instant::now();
+            strategy.align(&before, before.len(), &after, after.len());
+            let ms = start.elapsed().as_secs_f64() * 1000.0;
+            println!("{:>10} {:>8} {:>10.1}", format!("{:?}", strategy), rows, ms);
+        }
+    }
+}
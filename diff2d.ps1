@@ -26,7 +26,13 @@ if (-not (Test-Path $sqliteFilePath2 -PathType Leaf)) {
   Exit 1
 }
 
-$job = Start-Job -ScriptBlock { .\diff2d.exe $args[0] $args[1] 2> .\diff2d.log } -ArgumentList $sqliteFilePath1, $sqliteFilePath2
+$outputPath = Join-Path $env:TEMP "$(Split-Path $sqliteFilePath1 -Leaf)-$(Split-Path $sqliteFilePath2 -Leaf).xlsx"
+
+# diff2d exits with 0 when the files are the same, 1 when they differ and 2 on error
+$job = Start-Job -ScriptBlock {
+  $output = .\diff2d.exe $args[0] $args[1] --output $args[2] 2> .\diff2d.log
+  [pscustomobject]@{ Output = $output; ExitCode = $LASTEXITCODE }
+} -ArgumentList $sqliteFilePath1, $sqliteFilePath2, $outputPath
 
 # open a progress bar window
 $progressBar = New-Object System.Windows.Forms.Form
@@ -54,16 +60,17 @@ if ($job.State -eq "Failed") {
   Exit 1
 }
 
-# put job's output into a variable
-$jobOutput = Receive-Job -Job $job
+$result = Receive-Job -Job $job
 
-# when diff are same, jobOutput is actually 2 lines and first line is "same"
-$is_same = $jobOutput -split "'n" | Select-Object -First 1
-$filePath = $jobOutput -split "'n" | Select-Object -Last 1
+if ($result.ExitCode -eq 2) {
+  $errorMessage = Get-Content .\diff2d.log -Raw
+  [System.Windows.Forms.MessageBox]::Show($errorMessage, "Error", [System.Windows.Forms.MessageBoxButtons]::OK, [System.Windows.Forms.MessageBoxIcon]::Error)
+  Exit 1
+}
 
-if ($is_same -eq "same") {
-  $result = [System.Windows.Forms.MessageBox]::Show("두 파일은 동일합니다. 그래도 비교 엑셀 파일을 열까요?", "Info", [System.Windows.Forms.MessageBoxButtons]::YesNo, [System.Windows.Forms.MessageBoxIcon]::Information)
-  if ($result -ne "Yes") {
+if ($result.ExitCode -eq 0) {
+  $answer = [System.Windows.Forms.MessageBox]::Show("두 파일은 동일합니다. 그래도 비교 엑셀 파일을 열까요?", "Info", [System.Windows.Forms.MessageBoxButtons]::YesNo, [System.Windows.Forms.MessageBoxIcon]::Information)
+  if ($answer -ne "Yes") {
     Exit 0
   }
 }
@@ -75,7 +82,7 @@ $excel = New-Object -ComObject Excel.Application
 $excel.Visible = $true
 
 # Open the Excel file
-$workbook = $excel.Workbooks.Open($filePath)
+$workbook = $excel.Workbooks.Open($outputPath)
 
 # Cleanup - Release the Excel COM objects
 [System.Runtime.Interopservices.Marshal]::ReleaseComObject($workbook) | Out-Null
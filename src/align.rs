@@ -0,0 +1,804 @@
+use crate::fingerprint::{confirm, Fingerprints};
+use crate::lcs::{longest_increasing, Aligner, Strategy};
+use crate::sqlite::{DatumWithType, Type::Text};
+use anyhow::{Context, Result};
+use ndarray::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+/// A merged sequence as a pair of index maps into `before` and `after`.
+pub type Alignment = (Vec<Option<usize>>, Vec<Option<usize>>);
+
+/// Aligns two sequences of row keys by key equality instead of by position.
+///
+/// Returns the same shape as `lcs_core`: two equally long vectors describing the merged
+/// sequence, where each entry points into `before` and/or `after`.
+/// The merged sequence follows the order of `after`; rows only present in `before` are placed
+/// right after the row that preceded them in `before`.
+/// Duplicate keys are paired in order of appearance.
+pub fn key_core<K: Eq + Hash>(before: &[K], after: &[K]) -> Alignment {
+    let mut positions: HashMap<&K, VecDeque<usize>> = HashMap::new();
+    for (j, key) in after.iter().enumerate() {
+        positions.entry(key).or_default().push_back(j);
+    }
+
+    let mut before_to_after = vec![None; before.len()];
+    let mut after_to_before = vec![None; after.len()];
+    for (i, key) in before.iter().enumerate() {
+        if let Some(j) = positions.get_mut(key).and_then(|q| q.pop_front()) {
+            before_to_after[i] = Some(j);
+            after_to_before[j] = Some(i);
+        }
+    }
+
+    // deleted rows are anchored to the after-position of the closest matched row above them
+    let mut leading = vec![];
+    let mut trailing: Vec<Vec<usize>> = vec![vec![]; after.len()];
+    let mut anchor = None;
+    for (i, matched) in before_to_after.iter().enumerate() {
+        match (matched, anchor) {
+            (Some(j), _) => anchor = Some(*j),
+            (None, Some(j)) => trailing[j].push(i),
+            (None, None) => leading.push(i),
+        }
+    }
+
+    let mut map_to_before = vec![];
+    let mut map_to_after = vec![];
+
+    for i in leading {
+        map_to_before.push(Some(i));
+        map_to_after.push(None);
+    }
+    for (j, deleted) in trailing.into_iter().enumerate() {
+        map_to_before.push(after_to_before[j]);
+        map_to_after.push(Some(j));
+        for i in deleted {
+            map_to_before.push(Some(i));
+            map_to_after.push(None);
+        }
+    }
+
+    (map_to_before, map_to_after)
+}
+
+/// Resolves key column names against the header row (row 0) of `table`.
+fn key_columns(table: &Array2<DatumWithType>, key: &[String], side: &str) -> Result<Vec<usize>> {
+    let header = table.row(0);
+    key.iter()
+        .map(|name| {
+            header
+                .iter()
+                .position(|h| h.datum_type == Text && h.datum == name.as_bytes())
+                .with_context(|| format!("key column `{}` not found in {} table", name, side))
+        })
+        .collect()
+}
+
+fn row_keys<'a>(
+    table: &'a Array2<DatumWithType>,
+    key: &[usize],
+    side: &str,
+) -> Result<Vec<Vec<&'a DatumWithType>>> {
+    let keys: Vec<Vec<_>> = (1..table.nrows())
+        .map(|i| key.iter().map(|&j| &table[[i, j]]).collect())
+        .collect();
+
+    let mut seen = HashMap::new();
+    for (i, row_key) in keys.iter().enumerate() {
+        if let Some(first) = seen.insert(row_key, i) {
+            let values: Vec<String> = row_key.iter().map(|v| v.to_string()).collect();
+            anyhow::bail!(
+                "duplicate key ({}) in {} table at rows {} and {}",
+                values.join(", "),
+                side,
+                first + 1,
+                i + 1
+            );
+        }
+    }
+    Ok(keys)
+}
+
+/// Aligns rows by the values of the key columns. Row 0 is the header and always maps to itself.
+fn key_align(
+    before: &Array2<DatumWithType>,
+    after: &Array2<DatumWithType>,
+    key: &[String],
+) -> Result<Alignment> {
+    let before_keys = row_keys(before, &key_columns(before, key, "before")?, "before")?;
+    let after_keys = row_keys(after, &key_columns(after, key, "after")?, "after")?;
+    Ok(with_header(key_core(&before_keys, &after_keys)))
+}
+
+/// Matches rows by their values in `columns` regardless of position, so that only rows
+/// occurring more often on one side are left unmatched.
+fn multiset_align(
+    before: &Fingerprints,
+    after: &Fingerprints,
+    columns: &[(usize, usize)],
+) -> Alignment {
+    let before_rows = before.rows(&columns.iter().map(|&(j, _)| j).collect::<Vec<_>>());
+    let after_rows = after.rows(&columns.iter().map(|&(_, j)| j).collect::<Vec<_>>());
+    if columns.is_empty() {
+        // without a shared column every row would have the same empty value
+        let (removed, added) = (before_rows.len() - 1, after_rows.len() - 1);
+        return with_header((
+            (0..removed).map(Some).chain(vec![None; added]).collect(),
+            vec![None; removed]
+                .into_iter()
+                .chain((0..added).map(Some))
+                .collect(),
+        ));
+    }
+    with_header(confirm(
+        key_core(&before_rows[1..], &after_rows[1..]),
+        |i, j| before.row_eq(i + 1, after, j + 1, columns),
+    ))
+}
+
+/// Pairs entries only in before with entries only in after that have equal values, so that
+/// an item that changed position is matched at its new place instead of reported as a
+/// deletion and an insertion.
+/// `before` and `after` hold the hashes of the values; `equal` confirms equal hashes.
+fn pair_equal(
+    (map_to_before, map_to_after): Alignment,
+    before: &[u64],
+    after: &[u64],
+    equal: impl Fn(usize, usize) -> bool,
+) -> Alignment {
+    let mut removed: HashMap<u64, VecDeque<usize>> = HashMap::new();
+    for (k, (b, a)) in map_to_before.iter().zip(&map_to_after).enumerate() {
+        if let (Some(i), None) = (b, a) {
+            removed.entry(before[*i]).or_default().push_back(k);
+        }
+    }
+
+    let mut pairs = vec![];
+    for (k, (b, a)) in map_to_before.iter().zip(&map_to_after).enumerate() {
+        if let (None, Some(j)) = (b, a) {
+            let deleted = removed.get_mut(&after[*j]).and_then(|queue| {
+                let position = queue
+                    .iter()
+                    .position(|&r| equal(map_to_before[r].unwrap(), *j))?;
+                queue.remove(position)
+            });
+            if let Some(deleted) = deleted {
+                pairs.push((deleted, k));
+            }
+        }
+    }
+    apply_pairs((map_to_before, map_to_after), &pairs)
+}
+
+/// Greedily picks `(similarity, removed, added)` candidates, most similar first,
+/// using each entry at most once.
+fn best_pairs(mut candidates: Vec<(f64, usize, usize)>) -> Vec<(usize, usize)> {
+    candidates.sort_by(|x, y| y.0.total_cmp(&x.0));
+    let mut used = HashSet::new();
+    let mut result = vec![];
+    for (_, r, a) in candidates {
+        if !used.contains(&r) && !used.contains(&a) {
+            used.insert(r);
+            used.insert(a);
+            result.push((r, a));
+        }
+    }
+    result
+}
+
+/// Merges each `(removed, added)` pair of entries into one matched entry at the position of
+/// the added entry.
+fn apply_pairs(
+    (mut map_to_before, map_to_after): Alignment,
+    pairs: &[(usize, usize)],
+) -> Alignment {
+    let mut is_paired = vec![false; map_to_before.len()];
+    for &(r, a) in pairs {
+        map_to_before[a] = map_to_before[r];
+        is_paired[r] = true;
+    }
+    map_to_before
+        .into_iter()
+        .zip(map_to_after)
+        .zip(is_paired)
+        .filter(|(_, is_paired)| !is_paired)
+        .map(|(entry, _)| entry)
+        .unzip()
+}
+
+/// Runs of unmatched rows with at most this many removed and added row pairs compare every
+/// pair; larger runs only compare rows sharing a value.
+const ALL_PAIRS: usize = 1 << 12;
+
+/// In large runs, values held by more added rows than this do not suggest pairs.
+const MAX_SHARED: usize = 16;
+
+/// Pairs removed and added rows within each run of unmatched rows when at least `threshold`
+/// of their values in `columns` are equal, so that a row whose identifying column was edited
+/// shows as modified.
+fn pair_similar_rows(
+    rows: Alignment,
+    before: &Fingerprints,
+    after: &Fingerprints,
+    columns: &[(usize, usize)],
+    threshold: f64,
+) -> Alignment {
+    if threshold > 1.0 || columns.is_empty() {
+        return rows;
+    }
+    let (map_to_before, map_to_after) = &rows;
+    let is_matched = |k: usize| map_to_before[k].is_some() && map_to_after[k].is_some();
+    let similarity = |r: usize, a: usize| {
+        let (ib, ia) = (map_to_before[r].unwrap(), map_to_after[a].unwrap());
+        let equal = columns
+            .iter()
+            .filter(|&&(jb, ja)| before.cell_eq(ib, jb, after, ia, ja))
+            .count();
+        let similarity = equal as f64 / columns.len() as f64;
+        (equal > 0 && similarity >= threshold).then_some((similarity, r, a))
+    };
+
+    let mut pairs = vec![];
+    let mut start = 0;
+    while start < map_to_before.len() {
+        if is_matched(start) {
+            start += 1;
+            continue;
+        }
+        let end = (start..map_to_before.len())
+            .find(|&k| is_matched(k))
+            .unwrap_or(map_to_before.len());
+        let removed: Vec<usize> = (start..end)
+            .filter(|&k| map_to_after[k].is_none())
+            .collect();
+        let added: Vec<usize> = (start..end)
+            .filter(|&k| map_to_before[k].is_none())
+            .collect();
+
+        let candidates = if removed.len() * added.len() <= ALL_PAIRS {
+            removed
+                .iter()
+                .flat_map(|&r| added.iter().map(move |&a| (r, a)))
+                .filter_map(|(r, a)| similarity(r, a))
+                .collect()
+        } else {
+            // only rows with at least one equal value can reach the threshold
+            let mut holders: HashMap<(usize, u64), Vec<usize>> = HashMap::new();
+            for &a in &added {
+                for (c, &(_, ja)) in columns.iter().enumerate() {
+                    let value = after.cell(map_to_after[a].unwrap(), ja);
+                    holders.entry((c, value)).or_default().push(a);
+                }
+            }
+            let mut candidates = vec![];
+            for &r in &removed {
+                let mut sharing: Vec<usize> = columns
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(c, &(jb, _))| {
+                        holders.get(&(c, before.cell(map_to_before[r].unwrap(), jb)))
+                    })
+                    .filter(|holders| holders.len() <= MAX_SHARED)
+                    .flatten()
+                    .copied()
+                    .collect();
+                sharing.sort_unstable();
+                sharing.dedup();
+                candidates.extend(sharing.into_iter().filter_map(|a| similarity(r, a)));
+            }
+            candidates
+        };
+        pairs.extend(best_pairs(candidates));
+        start = end;
+    }
+    apply_pairs(rows, &pairs)
+}
+
+/// Pairs columns only in before with columns only in after whose values agree on at least
+/// `threshold` of the matched rows, best matches first.
+fn pair_renamed_columns(
+    (map_to_before, map_to_after): Alignment,
+    rows: &Alignment,
+    before: &Fingerprints,
+    after: &Fingerprints,
+    threshold: f64,
+) -> Alignment {
+    let matched_rows: Vec<(usize, usize)> = rows
+        .0
+        .iter()
+        .zip(&rows.1)
+        .filter_map(|(b, a)| b.zip(*a))
+        .filter(|&(b, a)| b > 0 && a > 0)
+        .collect();
+    if matched_rows.is_empty() {
+        return (map_to_before, map_to_after);
+    }
+
+    let removed: Vec<usize> = (0..map_to_before.len())
+        .filter(|&k| map_to_after[k].is_none())
+        .collect();
+    let added: Vec<usize> = (0..map_to_after.len())
+        .filter(|&k| map_to_before[k].is_none())
+        .collect();
+    let mut candidates = vec![];
+    for &r in &removed {
+        for &a in &added {
+            let (jb, ja) = (map_to_before[r].unwrap(), map_to_after[a].unwrap());
+            let equal = matched_rows
+                .iter()
+                .filter(|&&(ib, ia)| before.cell_eq(ib, jb, after, ia, ja))
+                .count();
+            let similarity = equal as f64 / matched_rows.len() as f64;
+            if similarity >= threshold {
+                candidates.push((similarity, r, a));
+            }
+        }
+    }
+    apply_pairs((map_to_before, map_to_after), &best_pairs(candidates))
+}
+
+/// Marks the before rows or columns that moved: matched entries outside the longest run whose
+/// before order agrees with the merged order. Indexed by before position.
+fn find_moves((map_to_before, map_to_after): &Alignment, before_len: usize) -> Vec<bool> {
+    let matched: Vec<usize> = map_to_before
+        .iter()
+        .zip(map_to_after)
+        .filter_map(|(b, a)| a.and(*b))
+        .collect();
+    let mut result = vec![false; before_len];
+    for &i in &matched {
+        result[i] = true;
+    }
+    for k in longest_increasing(&matched) {
+        result[matched[k]] = false;
+    }
+    result
+}
+
+/// Aligns rows by the first column, pairing the header rows regardless of their content so
+/// that a renamed first column does not split the header.
+fn lcs_rows(before: &Fingerprints, after: &Fingerprints, strategy: Strategy) -> Alignment {
+    let before_first_row = before.column(0);
+    let after_first_row = after.column(0);
+    let equal = |i, j| before.cell_eq(i, 0, after, j, 0);
+    if before_first_row.is_empty() || after_first_row.is_empty() {
+        return confirm(
+            strategy.align(
+                &before_first_row,
+                before_first_row.len(),
+                &after_first_row,
+                after_first_row.len(),
+            ),
+            equal,
+        );
+    }
+
+    let before_body = &before_first_row[1..];
+    let after_body = &after_first_row[1..];
+    with_header(confirm(
+        strategy.align(before_body, before_body.len(), after_body, after_body.len()),
+        |i, j| equal(i + 1, j + 1),
+    ))
+}
+
+/// Shifts an alignment of body rows down by one and prepends the header row.
+fn with_header((map_to_before, map_to_after): Alignment) -> Alignment {
+    let shift = |map: Vec<Option<usize>>| {
+        std::iter::once(Some(0))
+            .chain(map.into_iter().map(|i| i.map(|i| i + 1)))
+            .collect()
+    };
+    (shift(map_to_before), shift(map_to_after))
+}
+
+/// Stably sorts merged rows by their values in `columns`, taken from after when the row
+/// exists there. Rows merged with a header row stay on top.
+fn sort_rows(
+    (map_to_before, map_to_after): Alignment,
+    before: &Array2<DatumWithType>,
+    after: &Array2<DatumWithType>,
+    columns: &[(usize, usize)],
+) -> Alignment {
+    let (mut header, mut body): (Vec<_>, Vec<_>) = map_to_before
+        .into_iter()
+        .zip(map_to_after)
+        .partition(|(b, a)| *b == Some(0) || *a == Some(0));
+
+    let row_key = |(b, a): &(Option<usize>, Option<usize>)| -> Vec<&DatumWithType> {
+        match (b, a) {
+            (_, Some(i)) => columns.iter().map(|&(_, j)| &after[[*i, j]]).collect(),
+            (Some(i), None) => columns.iter().map(|&(j, _)| &before[[*i, j]]).collect(),
+            (None, None) => vec![],
+        }
+    };
+    body.sort_by(|x, y| row_key(x).cmp(&row_key(y)));
+    header.extend(body);
+    header.into_iter().unzip()
+}
+
+/// How rows and columns are matched and ordered in the merged table.
+#[derive(Clone)]
+pub struct AlignOptions {
+    /// Treats each table as a multiset of rows: rows are matched by all shared columns
+    /// wherever they are, and only rows whose number of occurrences differs are reported.
+    pub unordered: bool,
+    /// Sorts merged rows by key, or by all shared columns when there is no key.
+    pub sort: bool,
+    /// Share of matched rows that must hold equal values for a removed and an added column
+    /// to be taken as one renamed column. Above 1 disables rename detection.
+    pub rename_threshold: f64,
+    /// Share of shared columns that must hold equal values for a removed and an added row
+    /// next to each other to be taken as one modified row. Applies without a key only;
+    /// above 1 disables row pairing.
+    pub row_threshold: f64,
+    /// How rows without a key, columns and the words or graphemes of text cells are aligned.
+    pub strategy: Strategy,
+}
+
+impl Default for AlignOptions {
+    fn default() -> Self {
+        AlignOptions {
+            unordered: false,
+            sort: false,
+            rename_threshold: 0.8,
+            row_threshold: 0.5,
+            strategy: Strategy::default(),
+        }
+    }
+}
+
+/// Aligns the rows and columns of two tables whose header is at row 0.
+///
+/// `key` names the columns identifying a row.
+/// Rows are matched by key when given, otherwise by aligning the first column.
+/// Columns are matched by aligning the header row.
+pub fn compare(
+    before: &Array2<DatumWithType>,
+    after: &Array2<DatumWithType>,
+    key: &[String],
+    options: &AlignOptions,
+) -> Result<IndexMap> {
+    // an empty file has no header row to align on
+    if before.nrows() == 0 || after.nrows() == 0 {
+        return Ok(IndexMap::replaced(before, after));
+    }
+
+    let before_prints = Fingerprints::new(before);
+    let after_prints = Fingerprints::new(after);
+
+    let before_header = before_prints.row(0);
+    let after_header = after_prints.row(0);
+    let header_equal = |i, j| before_prints.cell_eq(0, i, &after_prints, 0, j);
+    let columns = confirm(
+        options.strategy.align(
+            &before_header,
+            before_header.len(),
+            &after_header,
+            after_header.len(),
+        ),
+        header_equal,
+    );
+    let columns = pair_equal(columns, &before_header, &after_header, header_equal);
+    let shared_columns: Vec<(usize, usize)> = columns
+        .0
+        .iter()
+        .zip(&columns.1)
+        .filter_map(|(b, a)| b.zip(*a))
+        .collect();
+
+    let mut rows = if options.unordered {
+        multiset_align(&before_prints, &after_prints, &shared_columns)
+    } else if key.is_empty() {
+        let before_rows =
+            before_prints.rows(&shared_columns.iter().map(|&(j, _)| j).collect::<Vec<_>>());
+        let after_rows =
+            after_prints.rows(&shared_columns.iter().map(|&(_, j)| j).collect::<Vec<_>>());
+        let rows = lcs_rows(&before_prints, &after_prints, options.strategy);
+        // rows sharing no column are all equal over zero columns, yet have nothing in common
+        let rows = if shared_columns.is_empty() {
+            rows
+        } else {
+            pair_equal(rows, &before_rows, &after_rows, |i, j| {
+                before_prints.row_eq(i, &after_prints, j, &shared_columns)
+            })
+        };
+        pair_similar_rows(
+            rows,
+            &before_prints,
+            &after_prints,
+            &shared_columns,
+            options.row_threshold,
+        )
+    } else {
+        key_align(before, after, key)?
+    };
+    // moves are judged against the order rows were aligned in, before any sorting
+    let row_moved = if options.unordered {
+        vec![false; before.nrows()]
+    } else {
+        find_moves(&rows, before.nrows())
+    };
+
+    let columns = pair_renamed_columns(
+        columns,
+        &rows,
+        &before_prints,
+        &after_prints,
+        options.rename_threshold,
+    );
+    let column_moved = find_moves(&columns, before.ncols());
+    let (column_map_to_before, column_map_to_after) = columns;
+
+    if options.sort {
+        let sort_columns = if key.is_empty() {
+            shared_columns
+        } else {
+            let before_key = key_columns(before, key, "before")?;
+            let after_key = key_columns(after, key, "after")?;
+            before_key.into_iter().zip(after_key).collect()
+        };
+        rows = sort_rows(rows, before, after, &sort_columns);
+    }
+    let (row_map_to_before, row_map_to_after) = rows;
+
+    Ok(IndexMap {
+        row_map_to_before,
+        row_map_to_after,
+        row_moved,
+        is_keyed: !key.is_empty(),
+        column_map_to_before,
+        column_map_to_after,
+        column_moved,
+    })
+}
+
+/// Row and column alignment of two tables.
+///
+/// Each merged row `i` shows row `row_map_to_before[i]` of before and/or
+/// row `row_map_to_after[i]` of after, and likewise for columns.
+pub struct IndexMap {
+    pub row_map_to_before: Vec<Option<usize>>,
+    pub row_map_to_after: Vec<Option<usize>>,
+    /// Per before row, whether it is matched out of order, i.e. moved.
+    pub row_moved: Vec<bool>,
+    /// Whether rows were matched by key. Their order then carries no meaning, so a row
+    /// that only moved does not make the tables differ.
+    pub is_keyed: bool,
+    pub column_map_to_before: Vec<Option<usize>>,
+    pub column_map_to_after: Vec<Option<usize>>,
+    /// Per before column, whether it is matched out of order, i.e. moved.
+    pub column_moved: Vec<bool>,
+}
+
+impl IndexMap {
+    /// Every cell of `before` is deleted.
+    pub fn removed<T>(before: &Array2<T>) -> IndexMap {
+        let (rows, columns) = before.dim();
+        IndexMap {
+            row_map_to_before: (0..rows).map(Some).collect(),
+            row_map_to_after: vec![None; rows],
+            row_moved: vec![false; rows],
+            is_keyed: false,
+            column_map_to_before: (0..columns).map(Some).collect(),
+            column_map_to_after: vec![None; columns],
+            column_moved: vec![false; columns],
+        }
+    }
+
+    /// Every cell of `after` is added.
+    pub fn added<T>(after: &Array2<T>) -> IndexMap {
+        let (rows, columns) = after.dim();
+        IndexMap {
+            row_map_to_before: vec![None; rows],
+            row_map_to_after: (0..rows).map(Some).collect(),
+            row_moved: vec![],
+            is_keyed: false,
+            column_map_to_before: vec![None; columns],
+            column_map_to_after: (0..columns).map(Some).collect(),
+            column_moved: vec![],
+        }
+    }
+
+    /// Every cell of `before` is deleted and every cell of `after` is added.
+    pub fn replaced<T>(before: &Array2<T>, after: &Array2<T>) -> IndexMap {
+        let (removed, added) = (IndexMap::removed(before), IndexMap::added(after));
+        let chain = |before: Vec<Option<usize>>, after: Vec<Option<usize>>| {
+            before.into_iter().chain(after).collect()
+        };
+        IndexMap {
+            row_map_to_before: chain(removed.row_map_to_before, added.row_map_to_before),
+            row_map_to_after: chain(removed.row_map_to_after, added.row_map_to_after),
+            row_moved: removed.row_moved,
+            is_keyed: false,
+            column_map_to_before: chain(removed.column_map_to_before, added.column_map_to_before),
+            column_map_to_after: chain(removed.column_map_to_after, added.column_map_to_after),
+            column_moved: removed.column_moved,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare, key_core, AlignOptions};
+    use crate::testing::grid;
+    use ndarray::Array2;
+
+    #[test]
+    fn reordered_rows_are_matched() {
+        let (b, a) = key_core(&[1, 2, 3], &[3, 1, 2]);
+        assert_eq!(b, vec![Some(2), Some(0), Some(1)]);
+        assert_eq!(a, vec![Some(0), Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn deleted_rows_follow_their_predecessor() {
+        let (b, a) = key_core(&[0, 1, 2, 3], &[4, 2, 0]);
+        assert_eq!(b, vec![None, Some(2), Some(3), Some(0), Some(1)]);
+        assert_eq!(a, vec![Some(0), Some(1), None, Some(2), None]);
+    }
+
+    #[test]
+    fn unordered_rows_differ_only_in_multiplicity() {
+        let before = grid(&["name", "b", "a", "c", "a"]);
+        let after = grid(&["name", "a", "c", "b", "d"]);
+        let options = AlignOptions {
+            unordered: true,
+            sort: true,
+            ..AlignOptions::default()
+        };
+        let map = compare(&before, &after, &[], &options).unwrap();
+
+        assert_eq!(
+            map.row_map_to_before,
+            vec![Some(0), Some(2), Some(4), Some(1), Some(3), None]
+        );
+        assert_eq!(
+            map.row_map_to_after,
+            vec![Some(0), Some(1), None, Some(3), Some(2), Some(4)]
+        );
+    }
+
+    #[test]
+    fn keys_must_exist_and_identify_rows() {
+        let before = grid(&["id,v", "1,a", "2,b"]);
+        let after = grid(&["id,v", "1,a", "1,b"]);
+        let options = AlignOptions::default();
+
+        let error = compare(&before, &after, &["code".to_owned()], &options)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "key column `code` not found in before table"
+        );
+        let error = compare(&before, &after, &["id".to_owned()], &options)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "duplicate key (1) in after table at rows 1 and 2"
+        );
+        assert!(compare(&before, &before, &["id".to_owned()], &options).is_ok());
+    }
+
+    #[test]
+    fn tables_sharing_no_column_match_no_rows() {
+        let before = grid(&["log", "x", "y"]);
+        let after = grid(&["item", "p", "q", "r"]);
+        let matched = |options: &AlignOptions| {
+            let map = compare(&before, &after, &[], options).unwrap();
+            assert_eq!(map.row_map_to_before.len(), 6);
+            map.row_map_to_before
+                .iter()
+                .zip(&map.row_map_to_after)
+                .filter(|(b, a)| b.is_some() && a.is_some())
+                .count()
+        };
+        assert_eq!(matched(&AlignOptions::default()), 1);
+        let unordered = AlignOptions {
+            unordered: true,
+            ..AlignOptions::default()
+        };
+        assert_eq!(matched(&unordered), 1);
+    }
+
+    #[test]
+    fn moved_rows_are_paired_at_their_new_position() {
+        let before = grid(&["name", "a", "b", "c"]);
+        let after = grid(&["name", "c", "a", "b"]);
+        let map = compare(&before, &after, &[], &AlignOptions::default()).unwrap();
+
+        assert_eq!(
+            map.row_map_to_before,
+            vec![Some(0), Some(3), Some(1), Some(2)]
+        );
+        assert_eq!(
+            map.row_map_to_after,
+            vec![Some(0), Some(1), Some(2), Some(3)]
+        );
+        assert_eq!(map.row_moved, vec![false, false, false, true]);
+    }
+
+    #[test]
+    fn renamed_and_moved_columns_are_matched() {
+        let before = grid(&[
+            "id,hp,name",
+            "1,10,a",
+            "2,20,b",
+            "3,30,c",
+            "4,40,d",
+            "5,50,e",
+        ]);
+        let after = grid(&[
+            "name,id,max_hp",
+            "a,1,10",
+            "b,2,20",
+            "c,3,35",
+            "d,4,40",
+            "e,5,50",
+        ]);
+        let map = compare(&before, &after, &[], &AlignOptions::default()).unwrap();
+
+        assert_eq!(map.column_map_to_before, vec![Some(2), Some(0), Some(1)]);
+        assert_eq!(map.column_map_to_after, vec![Some(0), Some(1), Some(2)]);
+        assert_eq!(map.column_moved, vec![false, false, true]);
+        assert_eq!(map.row_map_to_before, (0..6).map(Some).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn rows_with_an_edited_first_column_are_paired() {
+        let before = grid(&["id,name,hp", "1,sword,5", "2,bow,3", "3,axe,2"]);
+        let after = grid(&["id,name,hp", "1,sword,5", "20,bow,3", "3,axe,2"]);
+        let map = compare(&before, &after, &[], &AlignOptions::default()).unwrap();
+
+        assert_eq!(map.row_map_to_before, (0..4).map(Some).collect::<Vec<_>>());
+        assert_eq!(map.row_map_to_after, (0..4).map(Some).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn empty_tables_have_nothing_to_align() {
+        let empty = Array2::default((0, 0));
+        let header = grid(&["id,name"]);
+        let options = AlignOptions::default();
+
+        let map = compare(&empty, &empty, &[], &options).unwrap();
+        assert!(map.row_map_to_before.is_empty() && map.column_map_to_before.is_empty());
+
+        let map = compare(&header, &empty, &[], &options).unwrap();
+        assert_eq!(map.row_map_to_before, vec![Some(0)]);
+        assert_eq!(map.row_map_to_after, vec![None]);
+        assert_eq!(map.column_map_to_before, vec![Some(0), Some(1)]);
+
+        let map = compare(&empty, &header, &[], &options).unwrap();
+        assert_eq!(map.row_map_to_after, vec![Some(0)]);
+        assert_eq!(map.column_map_to_after, vec![Some(0), Some(1)]);
+    }
+
+    #[test]
+    fn large_runs_pair_rows_sharing_values() {
+        // every id changes, so all rows form one run too large to compare pairwise
+        let rows = |offset: usize| {
+            let mut rows = vec!["id,name,hp".to_owned()];
+            rows.extend((0..100).map(|i| format!("{},item{},{}", i + offset, i, i % 3)));
+            rows
+        };
+        let (before, after) = (rows(0), rows(1000));
+        let before = grid(&before.iter().map(String::as_str).collect::<Vec<_>>());
+        let after = grid(&after.iter().map(String::as_str).collect::<Vec<_>>());
+
+        let map = compare(&before, &after, &[], &AlignOptions::default()).unwrap();
+        assert_eq!(
+            map.row_map_to_before,
+            (0..101).map(Some).collect::<Vec<_>>()
+        );
+        assert_eq!(map.row_map_to_after, (0..101).map(Some).collect::<Vec<_>>());
+
+        let options = AlignOptions {
+            row_threshold: 2.0,
+            ..AlignOptions::default()
+        };
+        let map = compare(&before, &after, &[], &options).unwrap();
+        assert_eq!(map.row_map_to_before.len(), 201);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::source::TableSource;
+use crate::sqlite::{DatumWithType, Type};
+use anyhow::{bail, Context, Result};
+use ndarray::Array2;
+use std::path::{Path, PathBuf};
+
+pub struct CsvOptions {
+    pub delimiter: u8,
+    /// Convert columns whose values all parse as numbers into `Integer` or `Real`,
+    /// and empty cells into `Null`. Otherwise every cell is `Text`.
+    pub infer_types: bool,
+}
+
+/// A delimited file holds a single table named after the file stem.
+pub struct CsvSource {
+    path: PathBuf,
+    table: String,
+    options: CsvOptions,
+}
+
+impl CsvSource {
+    pub fn new(path: &Path, options: CsvOptions) -> CsvSource {
+        let table = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        CsvSource {
+            path: path.to_owned(),
+            table,
+            options,
+        }
+    }
+}
+
+impl TableSource for CsvSource {
+    fn tables(&self) -> Result<Vec<String>> {
+        Ok(vec![self.table.clone()])
+    }
+
+    fn read_table(&self, table: &str) -> Result<Array2<DatumWithType>> {
+        if table != self.table {
+            bail!("table `{}` not found in {}", table, self.path.display());
+        }
+        read_csv_as_2d_array(&self.path, &self.options)
+    }
+
+    fn is_single_table(&self) -> bool {
+        true
+    }
+}
+
+/// Reads a delimited file in the same shape as `read_table_as_2d_array`:
+/// the header at row 0 followed by one row per record.
+/// Short records are padded with `Null`.
+pub fn read_csv_as_2d_array(path: &Path, options: &CsvOptions) -> Result<Array2<DatumWithType>> {
+    let mut reader = ::csv::ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+
+    let mut records = vec![];
+    for record in reader.records() {
+        let record = record.with_context(|| format!("failed to read {}", path.display()))?;
+        records.push(record.iter().map(str::to_owned).collect::<Vec<_>>());
+    }
+
+    let row_len = records.len();
+    let column_len = records.iter().map(Vec::len).max().unwrap_or(0);
+    let column_types: Vec<Type> = (0..column_len)
+        .map(|j| {
+            if options.infer_types {
+                infer_column_type(records.iter().skip(1).filter_map(|r| r.get(j)))
+            } else {
+                Type::Text
+            }
+        })
+        .collect();
+
+    let mut result: Array2<DatumWithType> =
+        Array2::from_shape_fn((row_len, column_len), |(_, _)| DatumWithType::default());
+    for (i, record) in records.into_iter().enumerate() {
+        for (j, value) in record.into_iter().enumerate() {
+            result[[i, j]] = if i == 0 {
+                DatumWithType {
+                    datum: value.into_bytes(),
+                    datum_type: Type::Text,
+                }
+            } else {
+                parse_cell(value, column_types[j], options.infer_types)
+            };
+        }
+    }
+    Ok(result)
+}
+
+fn infer_column_type<'a>(values: impl Iterator<Item = &'a String>) -> Type {
+    let mut result = Type::Null;
+    for value in values.filter(|v| !v.is_empty()) {
+        let value_type = if value.parse::<i64>().is_ok() {
+            Type::Integer
+        } else if value.parse::<f64>().is_ok_and(f64::is_finite) {
+            // "inf" and "nan" parse as well, and overflowing literals become infinite
+            Type::Real
+        } else {
+            return Type::Text;
+        };
+        result = match (result, value_type) {
+            (Type::Null, t) => t,
+            (Type::Integer, Type::Integer) => Type::Integer,
+            _ => Type::Real,
+        };
+    }
+    result
+}
+
+fn parse_cell(value: String, column_type: Type, infer_types: bool) -> DatumWithType {
+    if infer_types && value.is_empty() {
+        return DatumWithType::default();
+    }
+    match column_type {
+        Type::Integer => DatumWithType {
+            datum: value.parse::<i64>().unwrap().to_le_bytes().to_vec(),
+            datum_type: Type::Integer,
+        },
+        Type::Real => DatumWithType {
+            datum: value.parse::<f64>().unwrap().to_le_bytes().to_vec(),
+            datum_type: Type::Real,
+        },
+        _ => DatumWithType {
+            datum: value.into_bytes(),
+            datum_type: Type::Text,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn column_types_are_inferred() {
+        let values = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            infer_column_type(values(&["1", "", "2"]).iter()),
+            Type::Integer
+        );
+        assert_eq!(infer_column_type(values(&["1", "2.5"]).iter()), Type::Real);
+        assert_eq!(infer_column_type(values(&["1", "x"]).iter()), Type::Text);
+        for word in ["inf", "-Infinity", "NaN", "1e999"] {
+            assert_eq!(infer_column_type(values(&["1", word]).iter()), Type::Text);
+        }
+        assert_eq!(infer_column_type(values(&[""]).iter()), Type::Null);
+    }
+}
@@ -0,0 +1,131 @@
+//! Hashes of cells, rows and columns, computed once per table so that alignment compares
+//! integers. Equal hashes only suggest equal values; matches are confirmed by comparing the
+//! values themselves.
+
+use crate::align::Alignment;
+use crate::sqlite::DatumWithType;
+use ndarray::Array2;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// A table together with the hash of every cell.
+pub struct Fingerprints<'a> {
+    table: &'a Array2<DatumWithType>,
+    cells: Array2<u64>,
+}
+
+impl<'a> Fingerprints<'a> {
+    pub fn new(table: &'a Array2<DatumWithType>) -> Fingerprints<'a> {
+        Fingerprints {
+            table,
+            cells: table.map(hash),
+        }
+    }
+
+    /// Hash of cell `(i, j)`.
+    pub fn cell(&self, i: usize, j: usize) -> u64 {
+        self.cells[[i, j]]
+    }
+
+    /// Hashes of column `j`, one per row, header included.
+    pub fn column(&self, j: usize) -> Vec<u64> {
+        self.cells.column(j).to_vec()
+    }
+
+    /// Hashes of row `i`, one per column.
+    pub fn row(&self, i: usize) -> Vec<u64> {
+        self.cells.row(i).to_vec()
+    }
+
+    /// One hash per row over the cells in `columns`, header included.
+    pub fn rows(&self, columns: &[usize]) -> Vec<u64> {
+        (0..self.cells.nrows())
+            .map(|i| {
+                let mut hasher = DefaultHasher::new();
+                for &j in columns {
+                    self.cells[[i, j]].hash(&mut hasher);
+                }
+                hasher.finish()
+            })
+            .collect()
+    }
+
+    /// Whether cell `(i, j)` equals cell `(other_i, other_j)` of `other`.
+    pub fn cell_eq(
+        &self,
+        i: usize,
+        j: usize,
+        other: &Fingerprints,
+        other_i: usize,
+        other_j: usize,
+    ) -> bool {
+        self.cells[[i, j]] == other.cells[[other_i, other_j]]
+            && self.table[[i, j]] == other.table[[other_i, other_j]]
+    }
+
+    /// Whether row `i` equals row `other_i` of `other` in every pair of `columns`.
+    pub fn row_eq(
+        &self,
+        i: usize,
+        other: &Fingerprints,
+        other_i: usize,
+        columns: &[(usize, usize)],
+    ) -> bool {
+        columns
+            .iter()
+            .all(|&(j, other_j)| self.cell_eq(i, j, other, other_i, other_j))
+    }
+}
+
+fn hash(datum: &DatumWithType) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    datum.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Splits matched entries whose values turn out to differ despite equal hashes into an
+/// insertion followed by a deletion.
+pub fn confirm(
+    (map_to_before, map_to_after): Alignment,
+    equal: impl Fn(usize, usize) -> bool,
+) -> Alignment {
+    let mut result: Alignment = (vec![], vec![]);
+    for (b, a) in map_to_before.into_iter().zip(map_to_after) {
+        match (b, a) {
+            (Some(i), Some(j)) if !equal(i, j) => {
+                result.0.extend([None, Some(i)]);
+                result.1.extend([Some(j), None]);
+            }
+            _ => {
+                result.0.push(b);
+                result.1.push(a);
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::grid;
+
+    #[test]
+    fn equal_hashes_are_confirmed() {
+        let (before, after) = (grid(&["a,b", "c,d"]), grid(&["x,b", "c,d"]));
+        let (before, after) = (Fingerprints::new(&before), Fingerprints::new(&after));
+
+        assert_ne!(before.rows(&[0, 1])[0], after.rows(&[0, 1])[0]);
+        assert_eq!(before.rows(&[0, 1])[1], after.rows(&[0, 1])[1]);
+        assert!(before.row_eq(1, &after, 1, &[(0, 0), (1, 1)]));
+        assert!(!before.cell_eq(0, 0, &after, 0, 0));
+
+        // pretend row 0 collided with row 0
+        let alignment = (vec![Some(0), Some(1)], vec![Some(0), Some(1)]);
+        let confirmed = confirm(alignment, |i, j| {
+            before.row_eq(i, &after, j, &[(0, 0), (1, 1)])
+        });
+        assert_eq!(confirmed.0, vec![None, Some(0), Some(1)]);
+        assert_eq!(confirmed.1, vec![Some(0), None, Some(1)]);
+    }
+}
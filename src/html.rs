@@ -0,0 +1,324 @@
+use crate::lcs::LcsStrResult;
+use crate::model::{CellChange, CellDiff, Change, RowDiff, TableDiff, TableStatus};
+use crate::sqlite::{DatumWithType, Type};
+use anyhow::Result;
+use std::io::Write;
+
+/// Unchanged rows kept visible around a change. Longer unchanged runs are collapsed.
+const CONTEXT_ROWS: usize = 3;
+
+const STYLE: &str = r#"
+/* the bar keeps one line, scrolling sideways, so the column headers can stick below it */
+:root { --header-height: 30px; }
+body { font-family: sans-serif; font-size: 13px; margin: 0; }
+header { position: sticky; top: 0; z-index: 3; background: #333; color: #fff; padding: 0 12px; height: var(--header-height); line-height: var(--header-height); white-space: nowrap; overflow-x: auto; overflow-y: hidden; }
+header a { color: #fff; margin-right: 12px; }
+section { margin: 12px; }
+h2 { font-size: 16px; }
+table.diff { border-collapse: collapse; }
+table.diff td, table.diff th { border: 1px solid #999; padding: 2px 6px; white-space: pre-wrap; vertical-align: top; }
+table.diff thead th { position: sticky; top: var(--header-height); z-index: 2; background: #eee; }
+table.diff td.gap, table.diff th.gap { border: none; background: #fff; min-width: 12px; }
+.added { background: #b7e4b7; }
+.removed { background: #f4a6a6; }
+.modified { background: #fff3a0; }
+.absent { background: #c8c8c8; }
+.moved { background: #bdd7ee; }
+.renamed { background: #f8cbad; }
+td.gap.moved { color: #1f4e79; white-space: nowrap; }
+.null { color: #888; font-style: italic; }
+span.ins { color: #008000; font-weight: bold; }
+span.del { color: #d00000; font-weight: bold; }
+tr.fold td { background: #f4f4f4; color: #555; cursor: pointer; text-align: center; }
+tbody.folded { display: none; }
+.status-added { color: #008000; } .status-removed { color: #d00000; }
+.status-changed { color: #a07000; } .status-identical { color: #888; }
+"#;
+
+const SCRIPT: &str = r#"
+document.querySelectorAll('tr.fold').forEach(function (row) {
+  row.addEventListener('click', function () {
+    row.parentElement.nextElementSibling.classList.toggle('folded');
+  });
+});
+"#;
+
+/// Writes a single self-contained HTML page with one side-by-side table per diff,
+/// mirroring the workbook layout. `labels` make up the page title and table captions.
+pub fn write_html(
+    tables: &[TableDiff],
+    labels: (&str, &str),
+    mut writer: impl Write,
+) -> Result<()> {
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(writer, "<html><head><meta charset=\"utf-8\">")?;
+    writeln!(
+        writer,
+        "<title>{} - {}</title>",
+        escape(labels.0),
+        escape(labels.1)
+    )?;
+    writeln!(writer, "<style>{}</style></head><body>", STYLE)?;
+
+    write_summary(&mut writer, tables)?;
+    for (t, table) in tables.iter().enumerate() {
+        write_table(&mut writer, t, table, labels)?;
+    }
+
+    writeln!(writer, "<script>{}</script></body></html>", SCRIPT)?;
+    Ok(())
+}
+
+fn write_summary(writer: &mut impl Write, tables: &[TableDiff]) -> Result<()> {
+    let count = |status: TableStatus| tables.iter().filter(|t| t.status == status).count();
+    write!(
+        writer,
+        "<header>{} tables: {} changed, {} added, {} removed, {} identical &mdash; ",
+        tables.len(),
+        count(TableStatus::Changed),
+        count(TableStatus::Added),
+        count(TableStatus::Removed),
+        count(TableStatus::Identical),
+    )?;
+    for (t, table) in tables.iter().enumerate() {
+        write!(
+            writer,
+            "<a href=\"#table-{}\" class=\"status-{}\">{}</a>",
+            t,
+            table.status.label(),
+            escape(&table.name)
+        )?;
+    }
+    writeln!(writer, "</header>")?;
+    Ok(())
+}
+
+fn write_table(
+    writer: &mut impl Write,
+    t: usize,
+    table: &TableDiff,
+    labels: (&str, &str),
+) -> Result<()> {
+    let count = |change: Change| table.changed_rows().filter(|r| r.change == change).count();
+    writeln!(writer, "<section id=\"table-{}\">", t)?;
+    writeln!(
+        writer,
+        "<h2>{} <span class=\"status-{}\">{}</span></h2>",
+        escape(&table.name),
+        table.status.label(),
+        table.status.label()
+    )?;
+    writeln!(
+        writer,
+        "<p>{} rows modified, {} added, {} removed, {} moved</p>",
+        count(Change::Modified),
+        count(Change::Added),
+        count(Change::Removed),
+        count(Change::Moved)
+    )?;
+
+    let columns = table.columns.len();
+    let (before_label, after_label) = table.side_labels(labels, "");
+    writeln!(writer, "<table class=\"diff\"><thead>")?;
+    writeln!(
+        writer,
+        "<tr><th colspan=\"{}\">{}</th><th class=\"gap\"></th><th colspan=\"{}\">{}</th></tr>",
+        columns,
+        escape(&before_label),
+        columns,
+        escape(&after_label)
+    )?;
+    if let Some(header) = table.rows.first() {
+        write_header(writer, table, header)?;
+    }
+    writeln!(writer, "</thead>")?;
+
+    let rows = &table.rows[1.min(table.rows.len())..];
+    let visible: Vec<bool> = (0..rows.len())
+        .map(|i| {
+            let start = i.saturating_sub(CONTEXT_ROWS);
+            let end = (i + CONTEXT_ROWS + 1).min(rows.len());
+            rows[start..end]
+                .iter()
+                .any(|row| row.change != Change::Unchanged)
+        })
+        .collect();
+
+    let mut i = 0;
+    while i < rows.len() {
+        let run = visible[i..]
+            .iter()
+            .take_while(|v| **v == visible[i])
+            .count();
+        if visible[i] {
+            writeln!(writer, "<tbody>")?;
+        } else {
+            writeln!(
+                writer,
+                "<tbody><tr class=\"fold\"><td colspan=\"{}\">&#x25B8; {} unchanged rows</td></tr></tbody><tbody class=\"folded\">",
+                columns * 2 + 1,
+                run
+            )?;
+        }
+        for row in &rows[i..i + run] {
+            write_row(writer, row)?;
+        }
+        writeln!(writer, "</tbody>")?;
+        i += run;
+    }
+
+    writeln!(writer, "</table></section>")?;
+    Ok(())
+}
+
+/// Renamed and moved columns are marked in the header instead of as removed and added.
+fn write_header(writer: &mut impl Write, table: &TableDiff, header: &RowDiff) -> Result<()> {
+    let names = [table.column_names(), table.after_column_names()];
+    write!(writer, "<tr>")?;
+    for (side, names) in names.iter().enumerate() {
+        if side == 1 {
+            write!(writer, "<th class=\"gap\"></th>")?;
+        }
+        for ((column, cell), name) in table.columns.iter().zip(&header.cells).zip(names) {
+            let value = if side == 0 { &cell.before } else { &cell.after };
+            let class = match (column.change, value) {
+                (_, None) => "absent",
+                (Change::Renamed, _) => "renamed",
+                (Change::Moved, _) => "moved",
+                (Change::Added, _) => "added",
+                (Change::Removed, _) => "removed",
+                _ => "",
+            };
+            write!(writer, "<th class=\"{}\">", class)?;
+            if value.is_some() {
+                write!(writer, "{}", escape(name))?;
+            }
+            write!(writer, "</th>")?;
+        }
+    }
+    writeln!(writer, "</tr>")?;
+    Ok(())
+}
+
+fn write_row(writer: &mut impl Write, row: &RowDiff) -> Result<()> {
+    let is_moved = row.change == Change::Moved;
+    write!(writer, "<tr>")?;
+    for cell in &row.cells {
+        write_cell(writer, cell, true, is_moved)?;
+    }
+    match (is_moved, row.before) {
+        // the position in the before table, which is not a row of this merged table
+        (true, Some(before)) => write!(
+            writer,
+            "<td class=\"gap moved\">&#x2190; before row {}</td>",
+            before
+        )?,
+        _ => write!(writer, "<td class=\"gap\"></td>")?,
+    }
+    for cell in &row.cells {
+        write_cell(writer, cell, false, is_moved)?;
+    }
+    writeln!(writer, "</tr>")?;
+    Ok(())
+}
+
+fn write_cell(
+    writer: &mut impl Write,
+    cell: &CellDiff,
+    is_before: bool,
+    is_moved: bool,
+) -> Result<()> {
+    let value = if is_before { &cell.before } else { &cell.after };
+    let class = match (cell.change, value) {
+        (_, None) | (CellChange::Absent, _) => "absent",
+        (CellChange::Unchanged, _) if is_moved => "moved",
+        (CellChange::Unchanged, _) => "",
+        (CellChange::Modified, _) => "modified",
+        (CellChange::Added, _) => "added",
+        (CellChange::Removed, _) => "removed",
+    };
+    write!(writer, "<td class=\"{}\">", class)?;
+
+    match value {
+        Some(_) if !cell.segments.is_empty() => {
+            for segment in &cell.segments {
+                match (segment, is_before) {
+                    (LcsStrResult::Both(s), _) => write!(writer, "{}", escape(s))?,
+                    (LcsStrResult::Deleted(s), true) => {
+                        write!(writer, "<span class=\"del\">{}</span>", escape(s))?
+                    }
+                    (LcsStrResult::Added(s), false) => {
+                        write!(writer, "<span class=\"ins\">{}</span>", escape(s))?
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Some(datum) => write_value(writer, datum)?,
+        None => {}
+    }
+
+    write!(writer, "</td>")?;
+    Ok(())
+}
+
+fn write_value(writer: &mut impl Write, datum: &DatumWithType) -> Result<()> {
+    match datum.datum_type {
+        Type::Null | Type::Blob => write!(
+            writer,
+            "<span class=\"null\">{}</span>",
+            escape(&datum.to_string())
+        )?,
+        _ => write!(writer, "{}", escape(&datum.to_string()))?,
+    }
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&#39;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::table_diff;
+
+    #[test]
+    fn changes_are_escaped_marked_and_folded() {
+        let unchanged = (2..12).map(|i| format!("{},item", i));
+        let rows = |first: &str| -> Vec<String> {
+            ["id,name".to_owned(), first.to_owned()]
+                .into_iter()
+                .chain(unchanged.clone())
+                .collect()
+        };
+        let (before, after) = (rows("1,old <sword>"), rows("1,new <sword>"));
+        let diff = table_diff(
+            &before.iter().map(String::as_str).collect::<Vec<_>>(),
+            &after.iter().map(String::as_str).collect::<Vec<_>>(),
+            &[],
+        );
+
+        let mut output = vec![];
+        write_html(&[diff], ("a&b", "c"), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("<title>a&amp;b - c</title>"));
+        assert!(output.contains("<span class=\"del\">old</span> &lt;sword&gt;"));
+        assert!(output.contains("<span class=\"ins\">new</span> &lt;sword&gt;"));
+        // three rows of context follow the change, the other seven are folded away
+        assert!(output.contains("7 unchanged rows</td></tr></tbody><tbody class=\"folded\">"));
+        assert!(!output.contains("<sword>"));
+    }
+}
@@ -0,0 +1,276 @@
+use crate::lcs::LcsStrResult;
+use crate::model::{CellChange, CellDiff, Change, RowDiff, TableDiff};
+use crate::sqlite::{DatumWithType, Type};
+use anyhow::Result;
+use serde::Serialize;
+use std::io::Write;
+
+/// Bumped whenever a field is renamed, removed or changes meaning.
+/// Adding fields does not change the version.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Document<'a> {
+    version: u32,
+    before: &'a str,
+    after: &'a str,
+    identical: bool,
+    tables: Vec<Table<'a>>,
+}
+
+#[derive(Serialize)]
+struct Table<'a> {
+    name: &'a str,
+    before_table: Option<&'a str>,
+    after_table: Option<&'a str>,
+    status: &'static str,
+    /// Merged row and column order as indices into the before and after grids.
+    row_map: IndexPairs,
+    column_map: IndexPairs,
+    columns: Vec<Column>,
+    /// Grid row indices, where row 0 is the header.
+    added_rows: Vec<usize>,
+    removed_rows: Vec<usize>,
+    /// Rows matched out of order, from their before to their after position.
+    moved_rows: Vec<Move>,
+    /// Names of added and removed columns.
+    added_columns: Vec<String>,
+    removed_columns: Vec<String>,
+    /// Columns matched by their values under a different name.
+    renamed_columns: Vec<Rename>,
+    /// Added, removed, modified and moved rows with their changed cells.
+    rows: Vec<Row<'a>>,
+}
+
+#[derive(Serialize)]
+struct IndexPairs {
+    before: Vec<Option<usize>>,
+    after: Vec<Option<usize>>,
+}
+
+#[derive(Serialize)]
+struct Move {
+    before: usize,
+    after: usize,
+}
+
+#[derive(Serialize)]
+struct Rename {
+    before: String,
+    after: String,
+}
+
+#[derive(Serialize)]
+struct Column {
+    name: String,
+    change: &'static str,
+    before: Option<usize>,
+    after: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct Row<'a> {
+    change: &'static str,
+    before: Option<usize>,
+    after: Option<usize>,
+    cells: Vec<Cell<'a>>,
+}
+
+#[derive(Serialize)]
+struct Cell<'a> {
+    column: String,
+    change: &'static str,
+    before: Option<Value>,
+    after: Option<Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    segments: Vec<Segment<'a>>,
+}
+
+#[derive(Serialize)]
+struct Value {
+    #[serde(rename = "type")]
+    value_type: &'static str,
+    value: serde_json::Value,
+}
+
+#[derive(Serialize)]
+struct Segment<'a> {
+    op: &'static str,
+    text: &'a str,
+}
+
+/// Writes every table diff as one JSON document, recording `labels` as the names of the
+/// before and after inputs.
+pub fn write_json(tables: &[TableDiff], labels: (&str, &str), writer: impl Write) -> Result<()> {
+    let document = Document {
+        version: SCHEMA_VERSION,
+        before: labels.0,
+        after: labels.1,
+        identical: tables.iter().all(|table| table.is_identical()),
+        tables: tables.iter().map(table).collect(),
+    };
+    serde_json::to_writer_pretty(writer, &document)?;
+    Ok(())
+}
+
+fn table(table: &TableDiff) -> Table<'_> {
+    let names = table.column_names();
+    let columns_with = |change: Change| {
+        table
+            .columns
+            .iter()
+            .zip(&names)
+            .filter(|(column, _)| column.change == change)
+            .map(|(_, name)| name.clone())
+            .collect()
+    };
+
+    Table {
+        name: &table.name,
+        before_table: table.before_table.as_deref(),
+        after_table: table.after_table.as_deref(),
+        status: table.status.label(),
+        row_map: IndexPairs {
+            before: table.rows.iter().map(|row| row.before).collect(),
+            after: table.rows.iter().map(|row| row.after).collect(),
+        },
+        column_map: IndexPairs {
+            before: table.columns.iter().map(|column| column.before).collect(),
+            after: table.columns.iter().map(|column| column.after).collect(),
+        },
+        columns: table
+            .columns
+            .iter()
+            .zip(&names)
+            .map(|(column, name)| Column {
+                name: name.clone(),
+                change: column.change.label(),
+                before: column.before,
+                after: column.after,
+            })
+            .collect(),
+        added_rows: table
+            .changed_rows()
+            .filter(|row| row.change == Change::Added)
+            .filter_map(|row| row.after)
+            .collect(),
+        removed_rows: table
+            .changed_rows()
+            .filter(|row| row.change == Change::Removed)
+            .filter_map(|row| row.before)
+            .collect(),
+        moved_rows: table
+            .changed_rows()
+            .filter(|row| row.change == Change::Moved)
+            .filter_map(|row| {
+                Some(Move {
+                    before: row.before?,
+                    after: row.after?,
+                })
+            })
+            .collect(),
+        added_columns: columns_with(Change::Added),
+        removed_columns: columns_with(Change::Removed),
+        renamed_columns: table
+            .columns
+            .iter()
+            .zip(names.iter().zip(table.after_column_names()))
+            .filter(|(column, _)| column.change == Change::Renamed)
+            .map(|(_, (before, after))| Rename {
+                before: before.clone(),
+                after,
+            })
+            .collect(),
+        rows: table.changed_rows().map(|r| row(r, &names)).collect(),
+    }
+}
+
+fn row<'a>(row: &'a RowDiff, names: &[String]) -> Row<'a> {
+    Row {
+        change: row.change.label(),
+        before: row.before,
+        after: row.after,
+        cells: row
+            .cells
+            .iter()
+            .zip(names)
+            .filter(|(cell, _)| {
+                !matches!(row.change, Change::Modified | Change::Moved) || is_changed(cell)
+            })
+            .map(|(cell, name)| Cell {
+                column: name.clone(),
+                change: cell.change.label(),
+                before: cell.before.as_ref().map(value),
+                after: cell.after.as_ref().map(value),
+                segments: cell.segments.iter().map(segment).collect(),
+            })
+            .collect(),
+    }
+}
+
+fn is_changed(cell: &CellDiff) -> bool {
+    cell.change != CellChange::Unchanged
+}
+
+fn value(datum: &DatumWithType) -> Value {
+    let (value_type, value) = match datum.datum_type {
+        Type::Null => ("null", serde_json::Value::Null),
+        Type::Integer => (
+            "integer",
+            i64::from_le_bytes(datum.datum.as_slice().try_into().unwrap()).into(),
+        ),
+        Type::Real => (
+            "real",
+            f64::from_le_bytes(datum.datum.as_slice().try_into().unwrap()).into(),
+        ),
+        Type::Text => (
+            "text",
+            String::from_utf8_lossy(&datum.datum).into_owned().into(),
+        ),
+        Type::Blob => (
+            "blob",
+            datum
+                .datum
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+                .into(),
+        ),
+    };
+    Value { value_type, value }
+}
+
+fn segment(segment: &LcsStrResult) -> Segment<'_> {
+    match segment {
+        LcsStrResult::Both(text) => Segment { op: "both", text },
+        LcsStrResult::Deleted(text) => Segment {
+            op: "deleted",
+            text,
+        },
+        LcsStrResult::Added(text) => Segment { op: "added", text },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::table_diff;
+
+    #[test]
+    fn added_column_is_listed() {
+        let diff = table_diff(&["id", "1"], &["id,hp", "1,9"], &[]);
+
+        let mut output = vec![];
+        write_json(&[diff], ("a", "b"), &mut output).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(json["version"], SCHEMA_VERSION);
+        assert_eq!(json["identical"], false);
+        assert_eq!(json["tables"][0]["added_columns"][0], "hp");
+        assert_eq!(json["tables"][0]["rows"][0]["cells"][0]["column"], "hp");
+        assert_eq!(
+            json["tables"][0]["rows"][0]["cells"][0]["after"]["value"],
+            "9"
+        );
+    }
+}
@@ -1,9 +1,13 @@
 use crate::lcs::LcsStrResult::{Added, Both, Deleted};
-use ndarray::{Array2, ArrayView1};
+use ndarray::Array2;
 use std::cmp::max;
-use std::ops::Index;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::{Index, Range};
+use std::str::FromStr;
 use unicode_segmentation::UnicodeSegmentation;
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum LcsStrResult {
     Both(String),
     Deleted(String),
@@ -13,7 +17,7 @@ pub enum LcsStrResult {
 fn split(text: &str) -> Vec<String> {
     // input: Lorem ipsum dolor sit amet
     // output: ["Lorem", " ", "ipsum", " ", "dolor", " ", "sit", " ", "amet"]
-    if text.len() == 0 {
+    if text.is_empty() {
         return vec![];
     }
 
@@ -22,7 +26,7 @@ fn split(text: &str) -> Vec<String> {
     for c in text.chars() {
         let is_whitespace = c.is_whitespace();
         if is_whitespace {
-            if current.len() > 0 {
+            if !current.is_empty() {
                 result.push(current.clone());
                 current = String::from("");
             }
@@ -31,16 +35,30 @@ fn split(text: &str) -> Vec<String> {
             current.push(c);
         }
     }
-    if current.len() > 0 {
+    if !current.is_empty() {
         result.push(current);
     }
     result
 }
 
-pub fn str_lcs_by_words<'a>(before: &'a str, after: &'a str) -> Vec<LcsStrResult> {
+/// Splits by words when either text contains a space, otherwise by graphemes.
+pub fn str_lcs(before: &str, after: &str, aligner: &impl Aligner) -> Vec<LcsStrResult> {
+    let has_whitespace = before.contains(' ') || after.contains(' ');
+    if has_whitespace {
+        str_lcs_by_words(before, after, aligner)
+    } else {
+        str_lcs_by_graphemes(before, after, aligner)
+    }
+}
+
+pub fn str_lcs_by_words<'a>(
+    before: &'a str,
+    after: &'a str,
+    aligner: &impl Aligner,
+) -> Vec<LcsStrResult> {
     let before: Vec<_> = split(before);
     let after: Vec<_> = split(after);
-    let (before_map, after_map) = vec_lcs(&before, &after);
+    let (before_map, after_map) = vec_lcs(&before, &after, aligner);
     let len_merged = before_map.len();
 
     if len_merged == 0 {
@@ -90,10 +108,14 @@ pub fn str_lcs_by_words<'a>(before: &'a str, after: &'a str) -> Vec<LcsStrResult
     result
 }
 
-pub fn str_lcs_by_graphemes<'a>(before: &'a str, after: &'a str) -> Vec<LcsStrResult> {
+pub fn str_lcs_by_graphemes<'a>(
+    before: &'a str,
+    after: &'a str,
+    aligner: &impl Aligner,
+) -> Vec<LcsStrResult> {
     let before: Vec<&str> = before.graphemes(true).collect();
     let after: Vec<&str> = after.graphemes(true).collect();
-    let (before_map, after_map) = vec_lcs(&before, &after);
+    let (before_map, after_map) = vec_lcs(&before, &after, aligner);
     let len_merged = before_map.len();
 
     if len_merged == 0 {
@@ -143,33 +165,579 @@ pub fn str_lcs_by_graphemes<'a>(before: &'a str, after: &'a str) -> Vec<LcsStrRe
     result
 }
 
-pub fn vec_lcs<'a, T: Eq>(
+pub fn vec_lcs<'a, T: Eq + Hash>(
     before: &'a Vec<T>,
     after: &'a Vec<T>,
+    aligner: &impl Aligner,
 ) -> (Vec<Option<usize>>, Vec<Option<usize>>) {
-    lcs_core(before, before.len(), after, after.len())
+    aligner.align(before, before.len(), after, after.len())
 }
 
-pub fn array_lcs<'a, T: Eq>(
-    before: &'a ArrayView1<&'a T>,
-    after: &'a ArrayView1<&'a T>,
-) -> (Vec<Option<usize>>, Vec<Option<usize>>) {
-    lcs_core(before, before.shape()[0], after, after.shape()[0])
+/// A way of aligning two sequences. Every aligner returns the shape `lcs_core` does.
+pub trait Aligner {
+    fn align<Container: Index<usize> + ?Sized>(
+        &self,
+        before: &Container,
+        before_size: usize,
+        after: &Container,
+        after_size: usize,
+    ) -> (Vec<Option<usize>>, Vec<Option<usize>>)
+    where
+        Container::Output: Eq + Hash;
+}
+
+/// Aligns along a longest common subsequence; see `lcs_core`.
+pub struct Lcs;
+
+/// Anchors on elements occurring exactly once on each side, in an order both sides agree
+/// on, and aligns the gaps between anchors the same way. Gaps without unique elements fall
+/// back to `lcs_core`. Keeps repeated values such as blank strings from pulling apart
+/// otherwise distinctive runs.
+pub struct Patience;
+
+/// Like `Patience`, but anchors on the common run whose elements occur least often in
+/// `before`, so it also finds anchors where no element is unique.
+pub struct Histogram;
+
+/// Selects an aligner at runtime.
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub enum Strategy {
+    #[default]
+    Lcs,
+    Patience,
+    Histogram,
+}
+
+impl FromStr for Strategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Strategy> {
+        match s.to_ascii_lowercase().as_str() {
+            "lcs" => Ok(Strategy::Lcs),
+            "patience" => Ok(Strategy::Patience),
+            "histogram" => Ok(Strategy::Histogram),
+            _ => anyhow::bail!(
+                "unknown strategy `{}`, expected lcs, patience or histogram",
+                s
+            ),
+        }
+    }
+}
+
+impl Aligner for Strategy {
+    fn align<Container: Index<usize> + ?Sized>(
+        &self,
+        before: &Container,
+        before_size: usize,
+        after: &Container,
+        after_size: usize,
+    ) -> (Vec<Option<usize>>, Vec<Option<usize>>)
+    where
+        Container::Output: Eq + Hash,
+    {
+        match self {
+            Strategy::Lcs => Lcs.align(before, before_size, after, after_size),
+            Strategy::Patience => Patience.align(before, before_size, after, after_size),
+            Strategy::Histogram => Histogram.align(before, before_size, after, after_size),
+        }
+    }
+}
+
+impl Aligner for Lcs {
+    fn align<Container: Index<usize> + ?Sized>(
+        &self,
+        before: &Container,
+        before_size: usize,
+        after: &Container,
+        after_size: usize,
+    ) -> (Vec<Option<usize>>, Vec<Option<usize>>)
+    where
+        Container::Output: Eq + Hash,
+    {
+        lcs_core(before, before_size, after, after_size)
+    }
+}
+
+impl Aligner for Patience {
+    fn align<Container: Index<usize> + ?Sized>(
+        &self,
+        before: &Container,
+        before_size: usize,
+        after: &Container,
+        after_size: usize,
+    ) -> (Vec<Option<usize>>, Vec<Option<usize>>)
+    where
+        Container::Output: Eq + Hash,
+    {
+        let mut merged = Vec::with_capacity(before_size + after_size);
+        patience(before, 0..before_size, after, 0..after_size, &mut merged);
+        slide_matches_forward(before, after, &mut merged);
+        merged.into_iter().unzip()
+    }
+}
+
+impl Aligner for Histogram {
+    fn align<Container: Index<usize> + ?Sized>(
+        &self,
+        before: &Container,
+        before_size: usize,
+        after: &Container,
+        after_size: usize,
+    ) -> (Vec<Option<usize>>, Vec<Option<usize>>)
+    where
+        Container::Output: Eq + Hash,
+    {
+        let mut merged = Vec::with_capacity(before_size + after_size);
+        let mut budget = HISTOGRAM_SCANS * (before_size + after_size) + 1024;
+        histogram(
+            before,
+            0..before_size,
+            after,
+            0..after_size,
+            &mut merged,
+            &mut budget,
+        );
+        slide_matches_forward(before, after, &mut merged);
+        merged.into_iter().unzip()
+    }
+}
+
+/// Appends the patience alignment of `before[rows]` with `after[columns]` to `merged`.
+fn patience<Container: Index<usize> + ?Sized>(
+    before: &Container,
+    rows: Range<usize>,
+    after: &Container,
+    columns: Range<usize>,
+    merged: &mut Vec<(Option<usize>, Option<usize>)>,
+) where
+    Container::Output: Eq + Hash,
+{
+    let (prefix, suffix) = common_ends(before, &rows, after, &columns);
+    merged.extend((0..prefix).map(|k| (Some(rows.start + k), Some(columns.start + k))));
+    let rows = rows.start + prefix..rows.end - suffix;
+    let columns = columns.start + prefix..columns.end - suffix;
+
+    // occurrences on each side and the last position seen, per distinct element
+    let mut counts: HashMap<&Container::Output, (usize, usize, usize, usize)> = HashMap::new();
+    for i in rows.clone() {
+        let entry = counts.entry(&before[i]).or_default();
+        entry.0 += 1;
+        entry.1 = i;
+    }
+    for j in columns.clone() {
+        if let Some(entry) = counts.get_mut(&after[j]) {
+            entry.2 += 1;
+            entry.3 = j;
+        }
+    }
+    let unique: Vec<(usize, usize)> = rows
+        .clone()
+        .filter_map(|i| match counts[&before[i]] {
+            (1, _, 1, j) => Some((i, j)),
+            _ => None,
+        })
+        .collect();
+
+    if unique.is_empty() {
+        lcs_range(before, rows.clone(), after, columns.clone(), merged, false);
+    } else {
+        let after_positions: Vec<usize> = unique.iter().map(|&(_, j)| j).collect();
+        let (mut i, mut j) = (rows.start, columns.start);
+        for k in longest_increasing(&after_positions) {
+            let (anchor_i, anchor_j) = unique[k];
+            patience(before, i..anchor_i, after, j..anchor_j, merged);
+            merged.push((Some(anchor_i), Some(anchor_j)));
+            (i, j) = (anchor_i + 1, anchor_j + 1);
+        }
+        patience(before, i..rows.end, after, j..columns.end, merged);
+    }
+
+    merged.extend(
+        (rows.end..rows.end + suffix)
+            .zip(columns.end..columns.end + suffix)
+            .map(|(i, j)| (Some(i), Some(j))),
+    );
 }
 
-pub fn lcs_core<'a, Container: Index<usize>>(
+/// Elements occurring more often than this in `before` are not used as histogram anchors.
+const MAX_OCCURRENCES: usize = 64;
+
+/// Every level of the histogram recursion scans its whole range, and an anchor near one end
+/// of the range makes for as many levels as elements. Once the scanned elements add up to
+/// this many times the input size, the ranges left are aligned with `patience` instead.
+const HISTOGRAM_SCANS: usize = 16;
+
+/// Appends the histogram alignment of `before[rows]` with `after[columns]` to `merged`,
+/// scanning at most `budget` elements.
+fn histogram<Container: Index<usize> + ?Sized>(
+    before: &Container,
+    rows: Range<usize>,
+    after: &Container,
+    columns: Range<usize>,
+    merged: &mut Vec<(Option<usize>, Option<usize>)>,
+    budget: &mut usize,
+) where
+    Container::Output: Eq + Hash,
+{
+    let Some(left) = budget.checked_sub(rows.len() + columns.len()) else {
+        patience(before, rows, after, columns, merged);
+        return;
+    };
+    *budget = left;
+
+    let (prefix, suffix) = common_ends(before, &rows, after, &columns);
+    merged.extend((0..prefix).map(|k| (Some(rows.start + k), Some(columns.start + k))));
+    let rows = rows.start + prefix..rows.end - suffix;
+    let columns = columns.start + prefix..columns.end - suffix;
+
+    let mut positions: HashMap<&Container::Output, Vec<usize>> = HashMap::new();
+    for i in rows.clone() {
+        positions.entry(&before[i]).or_default().push(i);
+    }
+
+    // the best common run so far as (occurrences, before start, after start, length)
+    let mut best: Option<(usize, usize, usize, usize)> = None;
+    let mut j = columns.start;
+    while j < columns.end {
+        let mut next = j + 1;
+        let candidates = positions.get(&after[j]).filter(|candidates| {
+            candidates.len() <= MAX_OCCURRENCES
+                && best.is_none_or(|(occurrences, ..)| candidates.len() <= occurrences)
+        });
+        for &i in candidates.into_iter().flatten() {
+            let (mut start_i, mut start_j) = (i, j);
+            while start_i > rows.start
+                && start_j > columns.start
+                && before[start_i - 1] == after[start_j - 1]
+            {
+                start_i -= 1;
+                start_j -= 1;
+            }
+            let (mut end_i, mut end_j) = (i + 1, j + 1);
+            while end_i < rows.end && end_j < columns.end && before[end_i] == after[end_j] {
+                end_i += 1;
+                end_j += 1;
+            }
+            let occurrences = (start_i..end_i)
+                .map(|k| positions[&before[k]].len())
+                .min()
+                .unwrap();
+            let length = end_i - start_i;
+            if best.is_none_or(|(best_occurrences, _, _, best_length)| {
+                occurrences < best_occurrences
+                    || (occurrences == best_occurrences && length > best_length)
+            }) {
+                best = Some((occurrences, start_i, start_j, length));
+            }
+            next = next.max(end_j);
+        }
+        j = next;
+    }
+
+    match best {
+        // no element of the range is rare, let alone unique to both sides
+        None => lcs_range(before, rows.clone(), after, columns.clone(), merged, false),
+        Some((_, i, j, length)) => {
+            histogram(
+                before,
+                rows.start..i,
+                after,
+                columns.start..j,
+                merged,
+                budget,
+            );
+            merged.extend(
+                (i..i + length)
+                    .zip(j..j + length)
+                    .map(|(i, j)| (Some(i), Some(j))),
+            );
+            histogram(
+                before,
+                i + length..rows.end,
+                after,
+                j + length..columns.end,
+                merged,
+                budget,
+            );
+        }
+    }
+
+    merged.extend(
+        (rows.end..rows.end + suffix)
+            .zip(columns.end..columns.end + suffix)
+            .map(|(i, j)| (Some(i), Some(j))),
+    );
+}
+
+/// Aligns `before` with `after` along a longest common subsequence.
+/// Returns, for every position of the merged sequence, the index into `before` and `after`;
+/// `None` on one side marks an element present only on the other.
+///
+/// Ties are broken like a backtrack through the full length table that walks from the end
+/// and prefers a deletion, then an insertion, then a match. The table is never built:
+/// Myers' O((n+m)·D) algorithm replays that backtrack while the number of edits D stays
+/// small, and Hirschberg's O(n·m) divide and conquer does otherwise as long as n·m stays
+/// below `EXACT_CELLS`. Larger inputs with many edits are first split at elements unique
+/// to both sides like `Patience` does, so the result may then be shorter than the longest.
+/// Either way memory stays linear in the input size.
+pub fn lcs_core<'a, Container: Index<usize> + ?Sized>(
     before: &'a Container,
     before_size: usize,
     after: &'a Container,
     after_size: usize,
 ) -> (Vec<Option<usize>>, Vec<Option<usize>>)
+where
+    Container::Output: Eq + Hash,
+{
+    let mut merged = Vec::with_capacity(before_size + after_size);
+    lcs_range(
+        before,
+        0..before_size,
+        after,
+        0..after_size,
+        &mut merged,
+        true,
+    );
+    merged.into_iter().unzip()
+}
+
+/// Lengths of the common prefix and the common suffix of `before[rows]` and `after[columns]`,
+/// not overlapping each other.
+fn common_ends<Container: Index<usize> + ?Sized>(
+    before: &Container,
+    rows: &Range<usize>,
+    after: &Container,
+    columns: &Range<usize>,
+) -> (usize, usize)
 where
     Container::Output: Eq,
 {
-    let mut memoization: Array2<usize> = Array2::zeros((before_size + 1, after_size + 1));
-    for i in 1..before_size + 1 {
-        for j in 1..after_size + 1 {
-            if before[i - 1] == after[j - 1] {
+    let shorter = rows.len().min(columns.len());
+    let prefix = (0..shorter)
+        .take_while(|&k| before[rows.start + k] == after[columns.start + k])
+        .count();
+    let suffix = (0..shorter - prefix)
+        .take_while(|&k| before[rows.end - 1 - k] == after[columns.end - 1 - k])
+        .count();
+    (prefix, suffix)
+}
+
+/// Blocks too large for exact alignment by divide and conquer, which takes time in
+/// proportion to their number of table cells.
+const EXACT_CELLS: usize = 1 << 26;
+
+/// Appends the alignment `lcs_core` describes of `before[rows]` with `after[columns]` to
+/// `merged`. `split` allows splitting a block too large for exact alignment with `patience`,
+/// and is false for blocks `patience` found no element unique to both sides in.
+fn lcs_range<Container: Index<usize> + ?Sized>(
+    before: &Container,
+    rows: Range<usize>,
+    after: &Container,
+    columns: Range<usize>,
+    merged: &mut Vec<(Option<usize>, Option<usize>)>,
+    split: bool,
+) where
+    Container::Output: Eq + Hash,
+{
+    // The backtrack always matches a common prefix, but not necessarily a common suffix:
+    // it deletes a repeated last element before matching the one ahead of it.
+    let (prefix, _) = common_ends(before, &rows, after, &columns);
+    merged.extend((0..prefix).map(|k| (Some(rows.start + k), Some(columns.start + k))));
+    let rows = rows.start + prefix..rows.end;
+    let columns = columns.start + prefix..columns.end;
+
+    if rows.is_empty() || columns.is_empty() {
+        merged.extend(columns.clone().map(|j| (None, Some(j))));
+        merged.extend(rows.clone().map(|i| (Some(i), None)));
+    } else if !myers(before, rows.clone(), after, columns.clone(), merged) {
+        if split && rows.len().saturating_mul(columns.len()) > EXACT_CELLS {
+            patience(before, rows, after, columns, merged);
+        } else {
+            lcs_block(before, rows, after, columns, merged);
+        }
+    }
+}
+
+/// Appends the alignment of `before[rows]` with `after[columns]` to `merged` using
+/// Myers' greedy algorithm. Gives up and returns `false` when the alignment needs more
+/// edits than a trace of linear size can hold.
+fn myers<Container: Index<usize> + ?Sized>(
+    before: &Container,
+    rows: Range<usize>,
+    after: &Container,
+    columns: Range<usize>,
+    merged: &mut Vec<(Option<usize>, Option<usize>)>,
+) -> bool
+where
+    Container::Output: Eq,
+{
+    let (n, m) = (rows.len() as isize, columns.len() as isize);
+    // The trace holds about D² positions; keep it within a few times the input size.
+    let max_edits = (((16 * (n + m) + 1024) as f64).sqrt() as isize).min(n + m);
+    let offset = max_edits + 1;
+    let index = |k: isize| (k + offset) as usize;
+
+    // furthest[k] is the furthest `before` position on diagonal k = x - y that at most d
+    // edits reach, or -1 when none does. Edits stay within the block, so the distance
+    // never decreases along a diagonal and every point up to furthest[k] is reachable.
+    let mut furthest = vec![-1; 2 * offset as usize + 1];
+    let mut trace: Vec<Vec<isize>> = vec![];
+    let mut edits = None;
+    for d in 0..=max_edits {
+        for k in (-d..=d).step_by(2) {
+            let inserted = match furthest[index(k + 1)] {
+                x if k < d && x >= 0 && x - k <= m => x,
+                _ => -1,
+            };
+            let deleted = match furthest[index(k - 1)] {
+                x if k > -d && x >= 0 && x < n => x + 1,
+                _ => -1,
+            };
+            let mut x = if d == 0 { 0 } else { inserted.max(deleted) };
+            x = x.max(furthest[index(k)]);
+            if x >= 0 {
+                while x < n
+                    && x - k < m
+                    && before[rows.start + x as usize] == after[columns.start + (x - k) as usize]
+                {
+                    x += 1;
+                }
+            }
+            furthest[index(k)] = x;
+        }
+        trace.push(furthest[index(-d)..=index(d)].to_vec());
+        if (n - m).abs() <= d && furthest[index(n - m)] >= n {
+            edits = Some(d);
+            break;
+        }
+    }
+    let Some(mut d) = edits else {
+        return false;
+    };
+
+    // Whether at most `d` edits reach `(x, y)`, i.e. whether the table backtrack may step
+    // there. Distances on a diagonal share its parity.
+    let reaches = |d: isize, x: isize, y: isize| {
+        let k = x - y;
+        let d = d - (d - k).rem_euclid(2);
+        d >= k.abs() && trace[d as usize][(k + d) as usize] >= x
+    };
+    let start = merged.len();
+    let (mut x, mut y) = (n, m);
+    while x > 0 || y > 0 {
+        if x > 0 && d > 0 && reaches(d - 1, x - 1, y) {
+            x -= 1;
+            d -= 1;
+            merged.push((Some(rows.start + x as usize), None));
+        } else if y > 0 && d > 0 && reaches(d - 1, x, y - 1) {
+            y -= 1;
+            d -= 1;
+            merged.push((None, Some(columns.start + y as usize)));
+        } else {
+            x -= 1;
+            y -= 1;
+            merged.push((
+                Some(rows.start + x as usize),
+                Some(columns.start + y as usize),
+            ));
+        }
+    }
+    merged[start..].reverse();
+    true
+}
+
+/// Moves runs of only insertions or only deletions past the match that follows them
+/// while that match can pair with the run's first element instead, so that matches are
+/// taken as early as possible.
+fn slide_matches_forward<Container: Index<usize> + ?Sized>(
+    before: &Container,
+    after: &Container,
+    merged: &mut [(Option<usize>, Option<usize>)],
+) where
+    Container::Output: Eq,
+{
+    let mut start = 0;
+    while start < merged.len() {
+        if let (Some(_), Some(_)) = merged[start] {
+            start += 1;
+            continue;
+        }
+        let mut end = start;
+        while end < merged.len() && !matches!(merged[end], (Some(_), Some(_))) {
+            end += 1;
+        }
+        let inserted = merged[start..end].iter().all(|&(row, _)| row.is_none());
+        let deleted = merged[start..end]
+            .iter()
+            .all(|&(_, column)| column.is_none());
+        while end < merged.len() {
+            let (first, next) = (merged[start], merged[end]);
+            let (Some(row), Some(column)) = next else {
+                break;
+            };
+            match first {
+                (None, Some(first)) if inserted && after[first] == after[column] => {
+                    merged[start] = (Some(row), Some(first));
+                    merged[end] = (None, Some(column));
+                }
+                (Some(first), None) if deleted && before[first] == before[row] => {
+                    merged[start] = (Some(first), Some(column));
+                    merged[end] = (Some(row), None);
+                }
+                _ => break,
+            }
+            start += 1;
+            end += 1;
+        }
+        start = end;
+    }
+}
+
+/// Blocks with at most this many table cells are solved with a full length table.
+const TABLE_CELLS: usize = 1 << 12;
+
+/// Appends the alignment of `before[rows]` with `after[columns]` to `merged`.
+fn lcs_block<Container: Index<usize> + ?Sized>(
+    before: &Container,
+    rows: Range<usize>,
+    after: &Container,
+    columns: Range<usize>,
+    merged: &mut Vec<(Option<usize>, Option<usize>)>,
+) where
+    Container::Output: Eq,
+{
+    if rows.len() < 2 || (rows.len() + 1) * (columns.len() + 1) <= TABLE_CELLS {
+        lcs_table(before, rows, after, columns, merged);
+        return;
+    }
+
+    let middle = rows.start + rows.len() / 2;
+    let split = crossing(before, rows.clone(), middle, after, columns.clone());
+    lcs_block(
+        before,
+        rows.start..middle,
+        after,
+        columns.start..split,
+        merged,
+    );
+    lcs_block(before, middle..rows.end, after, split..columns.end, merged);
+}
+
+/// Solves a block with a full length table and the backtrack `lcs_core` mimics.
+fn lcs_table<Container: Index<usize> + ?Sized>(
+    before: &Container,
+    rows: Range<usize>,
+    after: &Container,
+    columns: Range<usize>,
+    merged: &mut Vec<(Option<usize>, Option<usize>)>,
+) where
+    Container::Output: Eq,
+{
+    let (height, width) = (rows.len(), columns.len());
+    let mut memoization: Array2<usize> = Array2::zeros((height + 1, width + 1));
+    for i in 1..height + 1 {
+        for j in 1..width + 1 {
+            if before[rows.start + i - 1] == after[columns.start + j - 1] {
                 memoization[[i, j]] = memoization[[i - 1, j - 1]] + 1;
             } else {
                 memoization[[i, j]] = max(memoization[[i - 1, j]], memoization[[i, j - 1]]);
@@ -177,28 +745,248 @@ where
         }
     }
 
-    let mut i = before_size;
-    let mut j = after_size;
-    let len_merged: usize = before_size + after_size - memoization[[before_size, after_size]];
-    let mut map_to_before = vec![None; len_merged];
-    let mut map_to_after = vec![None; len_merged];
-
-    for k in (0..len_merged).rev() {
+    let start = merged.len();
+    let (mut i, mut j) = (height, width);
+    while i > 0 || j > 0 {
         if i > 0 && memoization[[i, j]] == memoization[[i - 1, j]] {
-            map_to_before[k] = Some(i - 1);
+            merged.push((Some(rows.start + i - 1), None));
             i -= 1;
         } else if j > 0 && memoization[[i, j]] == memoization[[i, j - 1]] {
-            map_to_after[k] = Some(j - 1);
+            merged.push((None, Some(columns.start + j - 1)));
             j -= 1;
         } else if i > 0 && j > 0 && memoization[[i - 1, j - 1]] + 1 == memoization[[i, j]] {
-            map_to_before[k] = Some(i - 1);
-            map_to_after[k] = Some(j - 1);
+            merged.push((Some(rows.start + i - 1), Some(columns.start + j - 1)));
             i -= 1;
             j -= 1;
         } else {
             unreachable!();
         }
     }
+    merged[start..].reverse();
+}
 
-    (map_to_before, map_to_after)
+/// Column of `after` at which the backtrack from the end of the block first reaches
+/// the table row of `middle`, i.e. the point where the block's alignment can be split.
+///
+/// Next to the LCS lengths, each cell records where a backtrack starting from it would
+/// cross the middle row, so a single forward pass suffices.
+fn crossing<Container: Index<usize> + ?Sized>(
+    before: &Container,
+    rows: Range<usize>,
+    middle: usize,
+    after: &Container,
+    columns: Range<usize>,
+) -> usize
+where
+    Container::Output: Eq,
+{
+    let mut lengths = vec![0; columns.len() + 1];
+    let mut crossings: Vec<usize> = (columns.start..columns.end + 1).collect();
+    for i in rows {
+        let mut diagonal = (0, columns.start);
+        for (j, column) in columns.clone().enumerate() {
+            let above = (lengths[j + 1], crossings[j + 1]);
+            let left = (lengths[j], crossings[j]);
+            let length = if before[i] == after[column] {
+                diagonal.0 + 1
+            } else {
+                max(above.0, left.0)
+            };
+            lengths[j + 1] = length;
+            if i >= middle {
+                crossings[j + 1] = if above.0 == length {
+                    above.1
+                } else if left.0 == length {
+                    left.1
+                } else {
+                    diagonal.1
+                };
+            }
+            diagonal = above;
+        }
+    }
+    crossings[columns.len()]
+}
+
+/// Indices of one longest strictly increasing subsequence of `values`.
+pub(crate) fn longest_increasing(values: &[usize]) -> Vec<usize> {
+    // tails[l] is the index of the smallest value ending an increasing run of length l + 1
+    let mut tails: Vec<usize> = vec![];
+    let mut previous = vec![None; values.len()];
+    for (k, &value) in values.iter().enumerate() {
+        let l = tails.partition_point(|&t| values[t] < value);
+        previous[k] = l.checked_sub(1).map(|l| tails[l]);
+        if l == tails.len() {
+            tails.push(k);
+        } else {
+            tails[l] = k;
+        }
+    }
+
+    let mut result = vec![];
+    let mut k = tails.last().copied();
+    while let Some(i) = k {
+        result.push(i);
+        k = previous[i];
+    }
+    result.reverse();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Merged = Vec<(Option<usize>, Option<usize>)>;
+
+    /// The original full-table implementation, kept as the reference for tie-breaking.
+    fn reference(before: &[u8], after: &[u8]) -> Merged {
+        let mut merged = vec![];
+        lcs_table(before, 0..before.len(), after, 0..after.len(), &mut merged);
+        merged
+    }
+
+    /// Random sequences over small alphabets, paired with a few random edits of them.
+    fn edited_pairs() -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+        (0..200)
+            .map(|_| {
+                let alphabet = next(4) + 1;
+                let before: Vec<u8> = (0..next(150)).map(|_| next(alphabet) as u8).collect();
+                let mut after = before.clone();
+                for _ in 0..next(20) {
+                    let at = next(after.len() as u64 + 1) as usize;
+                    match next(3) {
+                        0 if at < after.len() => {
+                            after.remove(at);
+                        }
+                        1 if at < after.len() => after[at] = next(alphabet) as u8,
+                        _ => after.insert(at, next(alphabet) as u8),
+                    }
+                }
+                (before, after)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn linear_space_matches_full_table() {
+        let pairs = edited_pairs();
+        for (before, after) in [(vec![2, 1], vec![1, 3, 0, 0, 2, 3])]
+            .into_iter()
+            .chain(pairs)
+        {
+            assert_eq!(
+                lcs_core(&before, before.len(), &after, after.len()),
+                reference(&before, &after).into_iter().unzip(),
+                "{:?} {:?}",
+                before,
+                after
+            );
+        }
+    }
+
+    #[test]
+    fn large_inputs_with_many_edits_are_split_at_unique_elements() {
+        // far more scattered changes than Myers' trace may hold, and too many cells for
+        // divide and conquer to finish in reasonable time
+        let before: Vec<usize> = (0..20_000).collect();
+        let after: Vec<usize> = (0..20_000)
+            .map(|i| if i % 7 == 3 { i + 20_000 } else { i })
+            .collect();
+        let (map_to_before, map_to_after) = lcs_core(&before, before.len(), &after, after.len());
+        assert_merges(&before, &after, &map_to_before, &map_to_after);
+        assert_eq!(map_to_before.len(), 20_000 + 20_000 / 7);
+    }
+
+    #[test]
+    fn many_edits_fall_back_to_divide_and_conquer() {
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+        let mut fallbacks = 0;
+        for _ in 0..50 {
+            // unrelated sequences mostly need more edits than Myers' trace may hold
+            let before: Vec<u8> = (0..100 + next(200)).map(|_| next(3) as u8).collect();
+            let after: Vec<u8> = (0..100 + next(200)).map(|_| next(3) as u8).collect();
+            let mut merged = vec![];
+            if !myers(
+                &before[..],
+                0..before.len(),
+                &after[..],
+                0..after.len(),
+                &mut merged,
+            ) {
+                fallbacks += 1;
+            }
+            assert_eq!(
+                lcs_core(&before, before.len(), &after, after.len()),
+                reference(&before, &after).into_iter().unzip(),
+                "{:?} {:?}",
+                before,
+                after
+            );
+        }
+        assert!(fallbacks > 25);
+    }
+
+    /// Every element appears once and in order, and matched elements are equal.
+    fn assert_merges<T: PartialEq>(
+        before: &[T],
+        after: &[T],
+        map_to_before: &[Option<usize>],
+        map_to_after: &[Option<usize>],
+    ) {
+        assert_eq!(map_to_before.len(), map_to_after.len());
+        assert!(map_to_before
+            .iter()
+            .zip(map_to_after)
+            .all(|(&i, &j)| match (i, j) {
+                (Some(i), Some(j)) => before[i] == after[j],
+                (None, None) => false,
+                _ => true,
+            }));
+        assert!(map_to_before.iter().flatten().copied().eq(0..before.len()));
+        assert!(map_to_after.iter().flatten().copied().eq(0..after.len()));
+    }
+
+    #[test]
+    fn patience_and_histogram_anchor_on_rare_elements() {
+        let before = vec!["", "", "x"];
+        let after = vec!["x", "", ""];
+        let (lcs_before, _) = Lcs.align(&before, 3, &after, 3);
+        assert_eq!(lcs_before, vec![None, Some(0), Some(1), Some(2)]);
+
+        for strategy in [Strategy::Patience, Strategy::Histogram] {
+            let (map_to_before, map_to_after) = strategy.align(&before, 3, &after, 3);
+            assert_eq!(map_to_before, vec![Some(0), Some(1), Some(2), None, None]);
+            assert_eq!(map_to_after, vec![None, None, Some(0), Some(1), Some(2)]);
+        }
+
+        // anchors at the start of every range used to make histogram quadratic
+        let before: Vec<usize> = (0..20_000).collect();
+        let after: Vec<usize> = (0..20_000)
+            .map(|i| if i % 2 == 1 { i + 20_000 } else { i })
+            .collect();
+        let (map_to_before, _) = Histogram.align(&before, before.len(), &after, after.len());
+        assert_eq!(map_to_before.len(), 30_000);
+
+        for (before, after) in edited_pairs() {
+            for strategy in [Strategy::Patience, Strategy::Histogram] {
+                let (map_to_before, map_to_after) =
+                    strategy.align(&before, before.len(), &after, after.len());
+                assert_merges(&before, &after, &map_to_before, &map_to_after);
+            }
+        }
+    }
 }
@@ -0,0 +1,264 @@
+//! Compares tables cell by cell and renders the differences side by side.
+//!
+//! Tables are read through a [`source::TableSource`], compared with [`diff_sources`]
+//! into a [`model::TableDiff`] per table, and rendered with [`workbook::write_workbook`],
+//! [`json::write_json`], [`html::write_html`] or [`terminal::write_text`].
+//!
+//! ```no_run
+//! use diff2d::source::{open_source, SourceOptions};
+//! use diff2d::{diff_sources, DiffOptions};
+//! use std::path::Path;
+//!
+//! let before = open_source(Path::new("before.sqlite"), &SourceOptions::default())?;
+//! let after = open_source(Path::new("after.sqlite"), &SourceOptions::default())?;
+//! let tables = diff_sources(before.as_ref(), after.as_ref(), &DiffOptions::default())?;
+//! let mut book = diff2d::workbook::write_workbook(&tables, ("before.sqlite", "after.sqlite"))?;
+//! book.save("diff.xlsx")?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use crate::align::{compare, AlignOptions, IndexMap};
+use crate::model::TableDiff;
+use crate::source::TableSource;
+use crate::tables::{pair_source_tables, TableSelection};
+use anyhow::{Context, Result};
+use ndarray::Array2;
+
+pub mod align;
+pub mod csv;
+pub mod fingerprint;
+pub mod html;
+pub mod json;
+pub mod lcs;
+pub mod model;
+pub mod schema;
+pub mod source;
+pub mod sqlite;
+pub mod stream;
+pub mod tables;
+pub mod terminal;
+#[cfg(test)]
+mod testing;
+pub mod workbook;
+pub mod xlsx;
+
+#[derive(Default)]
+pub struct DiffOptions {
+    /// Columns identifying a row in every table.
+    /// When empty, the declared primary key is used if both tables agree on it.
+    pub key: Vec<String>,
+    pub selection: TableSelection,
+    pub align: AlignOptions,
+}
+
+/// Compares every selected table of `before` with its counterpart in `after`.
+/// Tables only present on one side are reported as added or removed, except that two
+/// single-table sources such as csv files are always compared with each other.
+pub fn diff_sources(
+    before: &dyn TableSource,
+    after: &dyn TableSource,
+    options: &DiffOptions,
+) -> Result<Vec<TableDiff>> {
+    let tables1 = before.tables()?;
+    let tables2 = after.tables()?;
+    let pairs = pair_source_tables(before, &tables1, after, &tables2, &options.selection)?;
+    check_key(before, after, &pairs, &options.key)?;
+    pairs
+        .into_iter()
+        .map(|(table1, table2)| diff_table(before, table1, after, table2, options))
+        .collect()
+}
+
+/// Compares one pair of tables, either of which may be missing.
+pub(crate) fn diff_table(
+    before: &dyn TableSource,
+    table1: Option<&String>,
+    after: &dyn TableSource,
+    table2: Option<&String>,
+    options: &DiffOptions,
+) -> Result<TableDiff> {
+    let name = table1.or(table2).unwrap().clone();
+    let before_grid = match table1 {
+        Some(table) => before.read_table(table)?,
+        None => Array2::default((0, 0)),
+    };
+    let after_grid = match table2 {
+        Some(table) => after.read_table(table)?,
+        None => Array2::default((0, 0)),
+    };
+
+    let map = match (table1, table2) {
+        (Some(table1), Some(table2)) => {
+            let key = table_key(before, table1, after, table2, options)?;
+            compare(&before_grid, &after_grid, &key, &options.align)
+                .with_context(|| format!("failed to compare table `{}`", name))?
+        }
+        (Some(_), None) => IndexMap::removed(&before_grid),
+        (None, Some(_)) => IndexMap::added(&after_grid),
+        (None, None) => unreachable!(),
+    };
+
+    Ok(TableDiff::new(
+        name,
+        table1.cloned(),
+        table2.cloned(),
+        &before_grid,
+        &after_grid,
+        &map,
+        options.align.strategy,
+    ))
+}
+
+/// Checks before comparing anything that every table compared on both sides has the `key`
+/// columns, which are given once for all tables.
+pub(crate) fn check_key(
+    before: &dyn TableSource,
+    after: &dyn TableSource,
+    pairs: &[(Option<&String>, Option<&String>)],
+    key: &[String],
+) -> Result<()> {
+    let compared: Vec<_> = pairs
+        .iter()
+        .filter_map(|(table1, table2)| table1.zip(*table2))
+        .collect();
+    // a single table reports a missing key column when it is compared
+    if key.is_empty() || compared.len() < 2 {
+        return Ok(());
+    }
+    for (table1, table2) in compared {
+        for (source, table, side) in [(before, table1, "before"), (after, table2, "after")] {
+            let header = source.header(table)?;
+            if let Some(column) = key.iter().find(|column| !header.contains(column)) {
+                anyhow::bail!(
+                    "key column `{}` not found in {} table `{}`; use --table to compare only \
+                     the tables that have it",
+                    column,
+                    side,
+                    table
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Columns identifying a row of both tables: `options.key`, or else the primary key when
+/// both tables declare the same one and neither holds a NULL in it.
+pub(crate) fn table_key(
+    before: &dyn TableSource,
+    table1: &str,
+    after: &dyn TableSource,
+    table2: &str,
+    options: &DiffOptions,
+) -> Result<Vec<String>> {
+    if options.key.is_empty() {
+        primary_key_columns(before, table1, after, table2)
+    } else {
+        Ok(options.key.clone())
+    }
+}
+
+/// Names of the primary key columns, when both tables declare the same key and it
+/// identifies every row. SQLite allows several NULLs in most primary keys, so a key
+/// holding a NULL is not used and the rows are aligned by content instead.
+fn primary_key_columns(
+    input1: &dyn TableSource,
+    table1: &str,
+    input2: &dyn TableSource,
+    table2: &str,
+) -> Result<Vec<String>> {
+    let key1 = input1.primary_key(table1)?;
+    let key2 = input2.primary_key(table2)?;
+    if key1 != key2 || input1.has_null(table1, &key1)? || input2.has_null(table2, &key2)? {
+        Ok(vec![])
+    } else {
+        Ok(key1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sqlite::SqliteSource;
+    use rusqlite::Connection;
+
+    fn database(sql: &str) -> SqliteSource {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(sql).unwrap();
+        SqliteSource::from(conn)
+    }
+
+    #[test]
+    fn declared_primary_key_is_the_default_key() {
+        let before = database(
+            "CREATE TABLE t (code TEXT, v INT, PRIMARY KEY (code));
+             INSERT INTO t VALUES ('a', 1), ('b', 2);",
+        );
+        let after = database(
+            "CREATE TABLE t (code TEXT, v INT, PRIMARY KEY (code));
+             INSERT INTO t VALUES ('b', 2), ('a', 1);",
+        );
+        let options = DiffOptions::default();
+        assert_eq!(
+            table_key(&before, "t", &after, "t", &options).unwrap(),
+            ["code"]
+        );
+        let tables = diff_sources(&before, &after, &options).unwrap();
+        assert!(tables[0].is_identical());
+
+        let other = database("CREATE TABLE t (code TEXT, v INT PRIMARY KEY);");
+        assert!(table_key(&before, "t", &other, "t", &options)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn key_missing_from_a_table_is_reported_before_comparing() {
+        let sql = "CREATE TABLE a (id INT, v INT); CREATE TABLE b (code TEXT, v INT);";
+        let (before, after) = (database(sql), database(sql));
+        let options = DiffOptions {
+            key: vec!["id".to_owned()],
+            ..DiffOptions::default()
+        };
+        let error = diff_sources(&before, &after, &options).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "key column `id` not found in before table `b`; use --table to compare only the \
+             tables that have it"
+        );
+
+        let options = DiffOptions {
+            selection: TableSelection {
+                include: vec!["a".to_owned()],
+                ..TableSelection::default()
+            },
+            ..options
+        };
+        assert!(diff_sources(&before, &after, &options).is_ok());
+    }
+
+    #[test]
+    fn primary_keys_holding_nulls_align_by_content() {
+        let before = database(
+            "CREATE TABLE t (code TEXT PRIMARY KEY, v INT);
+             INSERT INTO t VALUES (NULL, 1), (NULL, 2), ('c', 3);",
+        );
+        let after = database(
+            "CREATE TABLE t (code TEXT PRIMARY KEY, v INT);
+             INSERT INTO t VALUES (NULL, 1), (NULL, 2), ('c', 4);",
+        );
+        let options = DiffOptions::default();
+        assert!(table_key(&before, "t", &after, "t", &options)
+            .unwrap()
+            .is_empty());
+        let tables = diff_sources(&before, &after, &options).unwrap();
+        assert_eq!(tables[0].changed_rows().count(), 1);
+
+        let explicit = DiffOptions {
+            key: vec!["code".to_owned()],
+            ..DiffOptions::default()
+        };
+        let error = diff_sources(&before, &after, &explicit).unwrap_err();
+        assert!(format!("{:#}", error).contains("duplicate key (NULL)"));
+    }
+}
@@ -1,369 +1,536 @@
-use crate::sqlite::Type::Text;
-use crate::sqlite::{get_tables, read_table_as_2d_array, DatumWithType, Type};
-use anyhow::Result;
-use lcs::*;
-use ndarray::prelude::*;
-use rust_xlsxwriter::{Color, Format, FormatBorder, Worksheet};
-
-fn diff<'a>(
-    before: &str,
-    after: &str,
-    green: &'a Format,
-    red: &'a Format,
-    black: &'a Format,
-) -> (Vec<(&'a Format, String)>, Vec<(&'a Format, String)>) {
-    let has_whitespace = before.contains(' ') || after.contains(' ');
-    let lcs_results = {
-        if has_whitespace {
-            str_lcs_by_words(before, after)
-        } else {
-            str_lcs_by_graphemes(before, after)
-        }
-    };
+use anyhow::{bail, Context, Result};
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use diff2d::align::AlignOptions;
+use diff2d::html::write_html;
+use diff2d::json::write_json;
+use diff2d::lcs::Strategy;
+use diff2d::model::{Change, TableDiff};
+use diff2d::schema::{diff_schemas, SchemaDiff};
+use diff2d::source::{open_source, Format, SourceOptions, TableSource};
+use diff2d::sqlite::SqliteSource;
+use diff2d::stream::stream_sources;
+use diff2d::tables::TableSelection;
+use diff2d::terminal::{use_color, write_schema, write_text, TextStream};
+use diff2d::workbook::{write_schema_sheet, write_workbook};
+use diff2d::{diff_sources, DiffOptions};
+use rust_xlsxwriter::Workbook;
+use std::ffi::OsString;
+use std::io::{BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// Compares tables in SQLite databases, csv/tsv files and spreadsheets cell by cell.
+///
+/// A delimited file is a single table named after the file, and two delimited files are
+/// compared with each other whatever their names; every worksheet is a table.
+/// Exits with 0 when identical, 1 when different and 2 on error.
+#[derive(Parser)]
+#[command(name = "diff2d", version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    diff: Option<DiffArgs>,
+}
 
-    let mut result = (vec![], vec![]);
+#[derive(Subcommand)]
+enum Command {
+    /// Render the cell-by-cell diff of every table (the default)
+    Diff(DiffArgs),
+    /// Print one line per table with its status and changed row counts
+    Summary(SummaryArgs),
+    /// Compare table structure without reading any rows: columns, keys, indexes,
+    /// triggers and views of SQLite databases, column names of other formats
+    Schema(SchemaArgs),
+}
 
-    for lcs_result in lcs_results {
-        match lcs_result {
-            LcsStrResult::Both(content) => {
-                result.0.push((black, content.clone()));
-                result.1.push((black, content));
-            }
-            LcsStrResult::Deleted(content) => {
-                result.0.push((red, content));
-            }
-            LcsStrResult::Added(content) => {
-                result.1.push((green, content));
-            }
-        }
-    }
+#[derive(Args)]
+struct InputArgs {
+    before: PathBuf,
+    after: PathBuf,
+
+    /// Read both inputs as this format instead of guessing from the file extension
+    #[arg(long, value_name = "sqlite|csv|tsv|xlsx")]
+    format: Option<Format>,
+
+    /// Read numeric csv columns as numbers and empty cells as NULL
+    #[arg(long)]
+    infer_types: bool,
+
+    /// 1-based worksheet row holding the column names
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    header_row: u32,
+
+    /// Compare only tables matching these names or glob patterns
+    #[arg(long, value_name = "NAME|GLOB", value_delimiter = ',')]
+    table: Vec<String>,
 
-    result
+    /// Skip tables matching these names or glob patterns
+    #[arg(long, value_name = "NAME|GLOB", value_delimiter = ',')]
+    exclude: Vec<String>,
+
+    /// Compare a before table with an after table of a different name
+    #[arg(long = "map", value_name = "BEFORE=AFTER", value_parser = parse_rename)]
+    rename: Vec<(String, String)>,
+
+    /// Print nothing and write no report; only set the exit code
+    #[arg(short, long)]
+    quiet: bool,
 }
 
-pub fn main() -> Result<()> {
-    let args: Vec<String> = std::env::args().collect();
-    let conn1 = rusqlite::Connection::open(&args[1])?;
-    let conn2 = rusqlite::Connection::open(&args[2])?;
+#[derive(Args)]
+struct SummaryArgs {
+    #[command(flatten)]
+    input: InputArgs,
 
-    let tables1 = get_tables(&conn1)?;
-    let table1 = tables1.first().unwrap();
-    let tables2 = get_tables(&conn2)?;
-    let table2 = tables2.first().unwrap();
+    /// Columns identifying a row, applied to every table; each compared table must have them
+    #[arg(long, value_name = "COLUMN", value_delimiter = ',')]
+    key: Vec<String>,
 
-    let before = read_table_as_2d_array(&conn1, table1)?;
-    let after = read_table_as_2d_array(&conn2, table2)?;
-    let map = compare(&before, &after);
+    /// Ignore row order and report only rows occurring more often on one side
+    #[arg(long)]
+    unordered: bool,
 
-    let mut book = rust_xlsxwriter::Workbook::new();
-    let sheet = book.add_worksheet();
+    /// Sort rows by key, or by all columns when there is no key
+    #[arg(long)]
+    sort: bool,
 
-    let (merged_row_len, merged_column_len) =
-        (map.row_map_to_before.len(), map.column_map_to_before.len());
+    /// Share of rows with equal values for a removed and an added column to count as renamed
+    #[arg(long, value_name = "RATIO", default_value_t = 0.8)]
+    rename_threshold: f64,
 
-    let (i0, j0) = (1, 0);
-    let (i1, j1) = (i0, j0 + 1 + merged_column_len as u16);
+    /// Share of equal cells for a removed and an added row to count as one modified row
+    #[arg(long, value_name = "RATIO", default_value_t = 0.5)]
+    row_threshold: f64,
 
-    {
-        let format = Format::new()
-            .set_background_color(Color::White)
-            .set_border(FormatBorder::None);
+    /// How rows, columns and the text of modified cells are aligned
+    #[arg(long, value_name = "lcs|patience|histogram", default_value = "lcs")]
+    strategy: Strategy,
+}
 
-        sheet.write_string_with_format(i0 - 1, j0, &args[1], &format)?;
+#[derive(Args)]
+struct SchemaArgs {
+    #[command(flatten)]
+    input: InputArgs,
 
-        sheet.write_string_with_format(i1 - 1, j1, &args[2], &format)?;
+    /// Save the report to this path instead of printing it, as a workbook when it ends in .xlsx
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct DiffArgs {
+    #[command(flatten)]
+    summary: SummaryArgs,
+
+    /// Where to write the report, `-` for stdout [default: stdout for json and text,
+    /// `<before>-<after>.<ext>` in the current directory otherwise]
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// Report format [default: guessed from the --output extension, else xlsx, or text
+    /// with --stream]
+    #[arg(long, value_enum)]
+    output_format: Option<OutputFormat>,
+
+    /// Compare SQLite tables with a key row by row in key order without loading them,
+    /// writing text as changes are found
+    #[arg(long, conflicts_with_all = ["unordered", "sort"])]
+    stream: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, ValueEnum)]
+enum OutputFormat {
+    Xlsx,
+    Json,
+    Html,
+    Text,
+}
+
+impl OutputFormat {
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "xlsx" => Some(OutputFormat::Xlsx),
+            "json" => Some(OutputFormat::Json),
+            "html" | "htm" => Some(OutputFormat::Html),
+            "txt" => Some(OutputFormat::Text),
+            _ => None,
+        }
     }
 
-    let mut is_same = true;
-
-    for i in 0..merged_row_len {
-        for j in 0..merged_column_len {
-            match (
-                map.row_map_to_before[i],
-                map.column_map_to_before[j],
-                map.row_map_to_after[i],
-                map.column_map_to_after[j],
-            ) {
-                (Some(ib), Some(jb), Some(ia), Some(ja)) => {
-                    if before[[ib, jb]] == after[[ia, ja]] {
-                        write_to_sheet(
-                            sheet,
-                            i0 + i as u32,
-                            j0 + j as u16,
-                            &before[[ib, jb]],
-                            Color::White,
-                        );
-                        write_to_sheet(
-                            sheet,
-                            i1 + i as u32,
-                            j1 + j as u16,
-                            &after[[ia, ja]],
-                            Color::White,
-                        );
-                    } else if before[[ib, jb]].datum_type == Text
-                        && after[[ia, ja]].datum_type == Text
-                    {
-                        is_same = false;
-                        let before_text = std::str::from_utf8(&before[[ib, jb]].datum).unwrap();
-                        let after_text = std::str::from_utf8(&after[[ia, ja]].datum).unwrap();
-
-                        let black = Format::new().set_font_color(Color::Black);
-                        let red = Format::new().set_font_color(Color::Red);
-                        let green = Format::new().set_font_color(Color::Green);
-                        let bg = Format::new().set_background_color(Color::Yellow);
-
-                        if before_text.is_empty() && after_text.is_empty() {
-                            // 여기 왜 왔지??
-                            continue;
-                        } else if before_text.is_empty() {
-                            sheet.write_rich_string_with_format(
-                                i1 + i as u32,
-                                j1 + j as u16,
-                                vec![(&green, after_text)].as_slice(),
-                                &bg,
-                            )?;
-                        } else if after_text.is_empty() {
-                            sheet.write_rich_string_with_format(
-                                i0 + i as u32,
-                                j0 + j as u16,
-                                vec![(&red, before_text)].as_slice(),
-                                &bg,
-                            )?;
-                        } else if before_text == after_text {
-                            // 여기 왜 왔지2 ??
-                            continue;
-                        }
-
-                        let (rich_input_1, rich_input_2) =
-                            diff(before_text, after_text, &green, &red, &black);
-
-                        let rich_input_1 = rich_input_1
-                            .iter()
-                            .map(|(f, s)| (*f, &**s))
-                            .filter(|(_, s)| !s.is_empty())
-                            .collect::<Vec<(&Format, &str)>>();
-
-                        let rich_input_2 = rich_input_2
-                            .iter()
-                            .map(|(f, s)| (*f, &**s))
-                            .filter(|(_, s)| !s.is_empty())
-                            .collect::<Vec<(&Format, &str)>>();
-
-                        if !rich_input_1.is_empty() {
-                            sheet.write_rich_string_with_format(
-                                i0 + i as u32,
-                                j0 + j as u16,
-                                rich_input_1.as_slice(),
-                                &bg,
-                            )?;
-                        }
-
-                        if !rich_input_2.is_empty() {
-                            sheet.write_rich_string_with_format(
-                                i1 + i as u32,
-                                j1 + j as u16,
-                                rich_input_2.as_slice(),
-                                &bg,
-                            )?;
-                        }
-                    } else {
-                        is_same = false;
-                        write_to_sheet(
-                            sheet,
-                            i0 + i as u32,
-                            j0 + j as u16,
-                            &before[[ib, jb]],
-                            Color::Yellow,
-                        );
-                        write_to_sheet(
-                            sheet,
-                            i1 + i as u32,
-                            j1 + j as u16,
-                            &after[[ia, ja]],
-                            Color::Yellow,
-                        );
-                    }
-                }
-                (Some(ib), Some(jb), _, _) => {
-                    is_same = false;
-                    write_to_sheet(
-                        sheet,
-                        i0 + i as u32,
-                        j0 + j as u16,
-                        &before[[ib, jb]],
-                        Color::Red,
-                    );
-                    write_gray_blank(sheet, i1 + i as u32, j1 + j as u16);
-                }
-                (_, _, Some(ia), Some(ja)) => {
-                    is_same = false;
-                    write_gray_blank(sheet, i0 + i as u32, j0 + j as u16);
-                    write_to_sheet(
-                        sheet,
-                        i1 + i as u32,
-                        j1 + j as u16,
-                        &after[[ia, ja]],
-                        Color::Green,
-                    );
-                }
-                (None, _, None, _) => {
-                    unreachable!();
-                }
-                (_, None, _, None) => {
-                    unreachable!();
-                }
-                _ => {
-                    is_same = false;
-                    // ex) row 삭제 column 추가면 여기로 타는게 가능
-                    //  + 가 추가, -가 삭제라 했을 때 아래와 같은 그림
-                    // 이 unified 그림에서 (1,1)은 before에도 after에도 없다.
-                    // 그래서 둘 다 gray blank로 그린다.
-                    // |  a  | + c |
-                    // | - b | + - |
-                    write_gray_blank(sheet, i0 + i as u32, j0 + j as u16);
-                    write_gray_blank(sheet, i1 + i as u32, j1 + j as u16);
-                }
-            }
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Xlsx => "xlsx",
+            OutputFormat::Json => "json",
+            OutputFormat::Html => "html",
+            OutputFormat::Text => "txt",
         }
     }
+}
 
-    if is_same {
-        println!("same");
+fn parse_rename(value: &str) -> Result<(String, String)> {
+    let (before, after) = value
+        .split_once('=')
+        .with_context(|| format!("expected `<before>=<after>`, got `{}`", value))?;
+    Ok((before.to_owned(), after.to_owned()))
+}
+
+impl DiffArgs {
+    /// `--output-format`, else the format of the `--output` extension, else the default.
+    fn output_format(&self) -> OutputFormat {
+        let default = if self.stream {
+            OutputFormat::Text
+        } else {
+            OutputFormat::Xlsx
+        };
+        self.output_format
+            .or_else(|| self.output.as_deref().and_then(OutputFormat::from_path))
+            .unwrap_or(default)
     }
+}
 
-    let dir = std::env::temp_dir();
-    let rand = rand::random::<u32>();
-    let filename_1 = std::path::Path::file_name(std::path::Path::new(&args[1])).unwrap();
-    let filename_2 = std::path::Path::file_name(std::path::Path::new(&args[2])).unwrap();
-    let file_name = format!(
-        "{}-{}-{}.xlsx",
-        filename_1.to_str().unwrap(),
-        filename_2.to_str().unwrap(),
-        rand
-    );
-    let path = dir.join(file_name);
-    book.save(path.clone())
-        .expect(format!("failed to save {}", path.display()).as_str());
-    println!("{}", path.display());
-    Ok(())
+impl InputArgs {
+    fn source_options(&self) -> SourceOptions {
+        SourceOptions {
+            format: self.format,
+            infer_types: self.infer_types,
+            header_row: self.header_row - 1,
+        }
+    }
+
+    fn open(&self) -> Result<(Box<dyn TableSource>, Box<dyn TableSource>)> {
+        let options = self.source_options();
+        Ok((
+            open_source(&self.before, &options)?,
+            open_source(&self.after, &options)?,
+        ))
+    }
+
+    fn schema(&self) -> Result<Vec<SchemaDiff>> {
+        diff_schemas(
+            &self.before,
+            &self.after,
+            &self.source_options(),
+            &self.selection(),
+        )
+    }
+
+    fn selection(&self) -> TableSelection {
+        TableSelection {
+            include: self.table.clone(),
+            exclude: self.exclude.clone(),
+            rename: self.rename.clone(),
+        }
+    }
+
+    fn labels(&self) -> (String, String) {
+        (
+            self.before.display().to_string(),
+            self.after.display().to_string(),
+        )
+    }
 }
 
-fn write_gray_blank(sheet: &mut Worksheet, row: u32, column: u16) {
-    let format = Format::new().set_background_color(Color::Gray);
-    sheet.write_blank(row, column, &format).unwrap();
+impl SummaryArgs {
+    fn diff(&self) -> Result<Vec<TableDiff>> {
+        let (before, after) = self.input.open()?;
+        diff_sources(before.as_ref(), after.as_ref(), &self.options())
+    }
+
+    fn options(&self) -> DiffOptions {
+        DiffOptions {
+            key: self.key.clone(),
+            selection: self.input.selection(),
+            align: AlignOptions {
+                unordered: self.unordered,
+                sort: self.sort,
+                rename_threshold: self.rename_threshold,
+                row_threshold: self.row_threshold,
+                strategy: self.strategy,
+            },
+        }
+    }
 }
 
-mod sqlite;
-
-fn compare<T>(before: &Array2<T>, after: &Array2<T>) -> IndexMap
-where
-    T: Eq,
-{
-    let before_first_row: ArrayView1<_> = before.slice(s![.., 0]);
-    let after_first_row: ArrayView1<_> = after.slice(s![.., 0]);
-    let before_first_column: ArrayView1<_> = before.slice(s![0, ..]);
-    let after_first_column: ArrayView1<_> = after.slice(s![0, ..]);
-
-    let (row_map_to_before, row_map_to_after) = lcs_core(
-        &before_first_row,
-        before_first_row.shape()[0],
-        &after_first_row,
-        after_first_row.shape()[0],
-    );
-    let (column_map_to_before, column_map_to_after) = lcs_core(
-        &before_first_column,
-        before_first_column.shape()[0],
-        &after_first_column,
-        after_first_column.shape()[0],
-    );
-
-    IndexMap {
-        row_map_to_before,
-        row_map_to_after,
-        column_map_to_before,
-        column_map_to_after,
+pub fn main() -> ExitCode {
+    run(std::env::args_os())
+}
+
+/// Exit codes follow diff(1): 0 when identical, 1 when different, 2 on error.
+fn run(args: impl IntoIterator<Item = impl Into<OsString> + Clone>) -> ExitCode {
+    let command = match parse_command(args) {
+        Ok(command) => command,
+        Err(e) => {
+            let _ = e.print();
+            return ExitCode::from(e.exit_code() as u8);
+        }
+    };
+
+    // a panic is a bug, but must not be mistaken for a difference by scripts
+    let result = std::panic::catch_unwind(|| match command {
+        Command::Diff(args) => run_diff(&args),
+        Command::Summary(args) => run_summary(&args),
+        Command::Schema(args) => run_schema(&args),
+    });
+    match result {
+        Ok(Ok(true)) => ExitCode::SUCCESS,
+        Ok(Ok(false)) => ExitCode::from(1),
+        Ok(Err(e)) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::from(2)
+        }
+        Err(_) => ExitCode::from(2),
     }
 }
 
-struct IndexMap {
-    row_map_to_before: Vec<Option<usize>>,
-    row_map_to_after: Vec<Option<usize>>,
-    column_map_to_before: Vec<Option<usize>>,
-    column_map_to_after: Vec<Option<usize>>,
+/// Runs `diff` when no subcommand is given so that `diff2d <before> <after>` keeps working.
+fn parse_command(
+    args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+) -> Result<Command, clap::Error> {
+    let matches = Cli::command().try_get_matches_from(args)?;
+    if matches.subcommand().is_some() {
+        Command::from_arg_matches(&matches)
+    } else {
+        DiffArgs::from_arg_matches(&matches).map(Command::Diff)
+    }
 }
 
-fn write_to_sheet(
-    sheet: &mut Worksheet,
-    row: u32,
-    column: u16,
-    value: &DatumWithType,
-    color: Color,
-) {
-    let format = Format::new()
-        .set_background_color(color)
-        .set_border(FormatBorder::Thick);
-    match value {
-        DatumWithType {
-            datum,
-            datum_type: Type::Integer,
-        } => {
-            sheet
-                .write_number_with_format(
-                    row,
-                    column,
-                    i64::from_le_bytes(datum.as_slice().try_into().unwrap()) as f64,
-                    &format,
-                )
-                .unwrap();
+/// Returns whether every compared table is identical.
+fn run_diff(args: &DiffArgs) -> Result<bool> {
+    if args.stream {
+        return run_stream(args);
+    }
+    let tables = args.summary.diff()?;
+    let is_same = tables.iter().all(|table| table.is_identical());
+    if args.summary.input.quiet {
+        return Ok(is_same);
+    }
+
+    let labels = args.summary.input.labels();
+    let labels = (labels.0.as_str(), labels.1.as_str());
+    let format = args.output_format();
+    // the workbook gets a schema sheet when both inputs are databases
+    let input = &args.summary.input;
+    let options = input.source_options();
+    let schema = if format == OutputFormat::Xlsx
+        && options.format_of(&input.before) == Format::Sqlite
+        && options.format_of(&input.after) == Format::Sqlite
+    {
+        input.schema()?
+    } else {
+        vec![]
+    };
+
+    let path = match &args.output {
+        Some(path) if path.as_os_str() == "-" => None,
+        Some(path) => Some(path.clone()),
+        None if matches!(format, OutputFormat::Json | OutputFormat::Text) => None,
+        None => Some(default_output_path(labels.0, labels.1, format)),
+    };
+
+    let Some(path) = path else {
+        let stdout = std::io::stdout();
+        if format == OutputFormat::Xlsx && stdout.is_terminal() {
+            bail!("refusing to write an xlsx workbook to a terminal, redirect stdout or use --output <path>");
         }
-        DatumWithType {
-            datum,
-            datum_type: Type::Real,
-        } => {
-            sheet
-                .write_number_with_format(
-                    row,
-                    column,
-                    f64::from_le_bytes(datum.as_slice().try_into().unwrap()),
-                    &format,
-                )
-                .unwrap();
+        let color = format == OutputFormat::Text && use_color();
+        write_report(&tables, &schema, labels, format, stdout.lock(), color)?;
+        return Ok(is_same);
+    };
+
+    if is_same {
+        println!("same");
+    }
+    let file = std::fs::File::create(&path)
+        .with_context(|| format!("failed to save {}", path.display()))?;
+    write_report(&tables, &schema, labels, format, file, false)
+        .with_context(|| format!("failed to save {}", path.display()))?;
+    println!("{}", path.display());
+    Ok(is_same)
+}
+
+/// Diffs two databases as text without loading keyed tables into memory.
+fn run_stream(args: &DiffArgs) -> Result<bool> {
+    let input = &args.summary.input;
+    let source_options = input.source_options();
+    if source_options.format_of(&input.before) != Format::Sqlite
+        || source_options.format_of(&input.after) != Format::Sqlite
+    {
+        bail!("--stream compares SQLite databases only");
+    }
+    let format = args.output_format();
+    if format != OutputFormat::Text {
+        bail!("--stream writes text only");
+    }
+
+    let before = SqliteSource::open(&input.before)?;
+    let after = SqliteSource::open(&input.after)?;
+    let options = args.summary.options();
+    let labels = input.labels();
+    let labels = (labels.0.as_str(), labels.1.as_str());
+    if input.quiet {
+        let mut sink = TextStream::new(std::io::sink(), labels, false);
+        return stream_sources(&before, &after, &options, &mut sink);
+    }
+
+    match &args.output {
+        Some(path) if path.as_os_str() != "-" => {
+            let file = std::fs::File::create(path)
+                .with_context(|| format!("failed to save {}", path.display()))?;
+            let mut sink = TextStream::new(BufWriter::new(file), labels, false);
+            let is_same = stream_sources(&before, &after, &options, &mut sink)
+                .with_context(|| format!("failed to save {}", path.display()))?;
+            println!("{}", path.display());
+            Ok(is_same)
         }
-        DatumWithType {
-            datum,
-            datum_type: Type::Text,
-        } => {
-            sheet
-                .write_string_with_format(row, column, std::str::from_utf8(datum).unwrap(), &format)
-                .unwrap();
+        _ => {
+            let stdout = std::io::stdout();
+            let mut sink = TextStream::new(BufWriter::new(stdout.lock()), labels, use_color());
+            stream_sources(&before, &after, &options, &mut sink)
         }
-        DatumWithType {
-            datum,
-            datum_type: Type::Blob,
-        } => {
-            sheet
-                .write_string_with_format(row, column, "This is a Blob", &format)
-                .unwrap();
+    }
+}
+
+fn write_report(
+    tables: &[TableDiff],
+    schema: &[SchemaDiff],
+    labels: (&str, &str),
+    format: OutputFormat,
+    writer: impl Write,
+    color: bool,
+) -> Result<()> {
+    let mut writer = BufWriter::new(writer);
+    match format {
+        OutputFormat::Xlsx => {
+            let mut book = write_workbook(tables, labels)?;
+            if !schema.is_empty() {
+                write_schema_sheet(&mut book, schema)?;
+            }
+            writer.write_all(&book.save_to_buffer()?)?;
         }
-        DatumWithType {
-            datum,
-            datum_type: Type::Null,
-        } => {
-            sheet
-                .write_string_with_format(row, column, "This is a DBNull", &format)
-                .unwrap();
+        OutputFormat::Json => write_json(tables, labels, &mut writer)?,
+        OutputFormat::Html => write_html(tables, labels, &mut writer)?,
+        OutputFormat::Text => write_text(tables, labels, &mut writer, color)?,
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn run_summary(args: &SummaryArgs) -> Result<bool> {
+    let tables = args.diff()?;
+    if !args.input.quiet {
+        for table in &tables {
+            let count =
+                |change: Change| table.changed_rows().filter(|r| r.change == change).count();
+            println!(
+                "{:<9} {} ({} modified, {} added, {} removed, {} moved rows)",
+                table.status.label(),
+                table.name,
+                count(Change::Modified),
+                count(Change::Added),
+                count(Change::Removed),
+                count(Change::Moved)
+            );
         }
     }
+    Ok(tables.iter().all(|table| table.is_identical()))
+}
+
+fn run_schema(args: &SchemaArgs) -> Result<bool> {
+    let schema = args.input.schema()?;
+    let is_same = schema.is_empty();
+    if args.input.quiet {
+        return Ok(is_same);
+    }
+
+    let labels = args.input.labels();
+    let labels = (labels.0.as_str(), labels.1.as_str());
+    let Some(path) = &args.output else {
+        write_schema(&schema, labels, std::io::stdout().lock(), use_color())?;
+        return Ok(is_same);
+    };
+
+    if OutputFormat::from_path(path) == Some(OutputFormat::Xlsx) {
+        let mut book = Workbook::new();
+        write_schema_sheet(&mut book, &schema)?;
+        book.save(path)
+            .with_context(|| format!("failed to save {}", path.display()))?;
+    } else {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("failed to save {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        write_schema(&schema, labels, &mut writer, false)?;
+        writer.flush()?;
+    }
+    println!("{}", path.display());
+    Ok(is_same)
 }
 
-mod lcs;
+/// `<before>-<after>.<ext>` in the current directory, named after the input files.
+fn default_output_path(before: &str, after: &str, format: OutputFormat) -> PathBuf {
+    let file_name = |path: &str| {
+        Path::new(path).file_name().map_or_else(
+            || path.to_owned(),
+            |name| name.to_string_lossy().into_owned(),
+        )
+    };
+    PathBuf::from(format!(
+        "{}-{}.{}",
+        file_name(before),
+        file_name(after),
+        format.extension()
+    ))
+}
 
 #[cfg(test)]
 mod tests {
-    use crate::{lcs, main};
+    use crate::{default_output_path, main, run, Cli, OutputFormat};
+    use clap::CommandFactory;
+    use std::path::PathBuf;
+    use std::process::ExitCode;
 
     #[test]
     fn asdf() {
-        main();
+        let _ = main();
+    }
+
+    #[test]
+    fn default_output_path_is_named_after_inputs() {
+        let path = default_output_path("old/game.sqlite", "new/game.sqlite", OutputFormat::Html);
+        assert_eq!(path, PathBuf::from("game.sqlite-game.sqlite.html"));
+        assert_eq!(OutputFormat::from_path(&path), Some(OutputFormat::Html));
+    }
+
+    #[test]
+    fn exit_codes_tell_same_different_and_error() {
+        let dir = std::env::temp_dir().join(format!("diff2d-exit-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.csv");
+        let b = dir.join("b.csv");
+        let missing = dir.join("missing.db");
+        std::fs::write(&a, "id,v\n1,x\n").unwrap();
+        std::fs::write(&b, "id,v\n1,y\n").unwrap();
+
+        let exit = |before: &PathBuf, after: &PathBuf| {
+            run([
+                "diff2d".as_ref(),
+                before.as_os_str(),
+                after.as_os_str(),
+                "--quiet".as_ref(),
+            ])
+        };
+        assert_eq!(exit(&a, &a), ExitCode::SUCCESS);
+        assert_eq!(exit(&a, &b), ExitCode::from(1));
+        assert_eq!(exit(&missing, &a), ExitCode::from(2));
+        assert_eq!(exit(&a, &missing), ExitCode::from(2));
+        assert!(!missing.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cli() {
+        Cli::command().debug_assert();
     }
 }
@@ -0,0 +1,362 @@
+use crate::align::IndexMap;
+use crate::lcs::{str_lcs, LcsStrResult, Strategy};
+use crate::sqlite::{DatumWithType, Type};
+use ndarray::Array2;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum TableStatus {
+    Added,
+    Removed,
+    Changed,
+    Identical,
+}
+
+impl TableStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            TableStatus::Added => "added",
+            TableStatus::Removed => "removed",
+            TableStatus::Changed => "changed",
+            TableStatus::Identical => "identical",
+        }
+    }
+}
+
+/// How a row or a column differs between before and after.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Change {
+    Unchanged,
+    /// Present on both sides with at least one differing cell.
+    Modified,
+    Added,
+    Removed,
+    /// Matched out of order; its cells may differ as well.
+    Moved,
+    /// A column matched by its values under a different name.
+    Renamed,
+}
+
+impl Change {
+    pub fn label(self) -> &'static str {
+        match self {
+            Change::Unchanged => "unchanged",
+            Change::Modified => "modified",
+            Change::Added => "added",
+            Change::Removed => "removed",
+            Change::Moved => "moved",
+            Change::Renamed => "renamed",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum CellChange {
+    Unchanged,
+    Modified,
+    Added,
+    Removed,
+    /// The cell exists on neither side, e.g. a removed row crossing an added column.
+    Absent,
+}
+
+impl CellChange {
+    pub fn label(self) -> &'static str {
+        match self {
+            CellChange::Unchanged => "unchanged",
+            CellChange::Modified => "modified",
+            CellChange::Added => "added",
+            CellChange::Removed => "removed",
+            CellChange::Absent => "absent",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellDiff {
+    pub change: CellChange,
+    pub before: Option<DatumWithType>,
+    pub after: Option<DatumWithType>,
+    /// Inline word or grapheme changes, when both sides are differing text.
+    pub segments: Vec<LcsStrResult>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowDiff {
+    pub change: Change,
+    /// Row index in the before grid, where row 0 is the header.
+    pub before: Option<usize>,
+    /// Row index in the after grid, where row 0 is the header.
+    pub after: Option<usize>,
+    /// One cell per merged column.
+    pub cells: Vec<CellDiff>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDiff {
+    pub change: Change,
+    pub before: Option<usize>,
+    pub after: Option<usize>,
+}
+
+/// The differences of one table pair, in merged order.
+/// Row 0 of `rows` is the header row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableDiff {
+    /// The before table name, or the after table name when the table was added.
+    pub name: String,
+    pub before_table: Option<String>,
+    pub after_table: Option<String>,
+    pub status: TableStatus,
+    pub columns: Vec<ColumnDiff>,
+    pub rows: Vec<RowDiff>,
+}
+
+impl TableDiff {
+    /// Builds the diff of two grids aligned by `map`, aligning the text of modified cells
+    /// with `strategy`. A missing table is passed as an empty grid with `None` as its name.
+    pub fn new(
+        name: String,
+        before_table: Option<String>,
+        after_table: Option<String>,
+        before: &Array2<DatumWithType>,
+        after: &Array2<DatumWithType>,
+        map: &IndexMap,
+        strategy: Strategy,
+    ) -> TableDiff {
+        let columns: Vec<(Option<usize>, Option<usize>)> = map
+            .column_map_to_before
+            .iter()
+            .copied()
+            .zip(map.column_map_to_after.iter().copied())
+            .collect();
+
+        let rows: Vec<RowDiff> = map
+            .row_map_to_before
+            .iter()
+            .zip(&map.row_map_to_after)
+            .map(|(&ib, &ia)| {
+                let cells: Vec<CellDiff> = columns
+                    .iter()
+                    .map(|&(jb, ja)| {
+                        let before = ib.zip(jb).map(|(i, j)| &before[[i, j]]);
+                        let after = ia.zip(ja).map(|(i, j)| &after[[i, j]]);
+                        diff_cell(before, after, strategy)
+                    })
+                    .collect();
+                let change = match (ib, ia) {
+                    (Some(i), Some(_)) if map.row_moved[i] => Change::Moved,
+                    (Some(_), Some(_))
+                        if cells.iter().all(|c| c.change == CellChange::Unchanged) =>
+                    {
+                        Change::Unchanged
+                    }
+                    (Some(_), Some(_)) => Change::Modified,
+                    (Some(_), None) => Change::Removed,
+                    (None, _) => Change::Added,
+                };
+                RowDiff {
+                    change,
+                    before: ib,
+                    after: ia,
+                    cells,
+                }
+            })
+            .collect();
+
+        let columns: Vec<ColumnDiff> = columns
+            .iter()
+            .enumerate()
+            .map(|(j, &(before, after))| {
+                let is_renamed = rows
+                    .first()
+                    .is_some_and(|header| header.cells[j].change == CellChange::Modified);
+                let change = match (before, after) {
+                    (Some(_), Some(_)) if is_renamed => Change::Renamed,
+                    (Some(j), Some(_)) if map.column_moved[j] => Change::Moved,
+                    (Some(_), Some(_))
+                        if rows
+                            .iter()
+                            .all(|r| r.cells[j].change != CellChange::Modified) =>
+                    {
+                        Change::Unchanged
+                    }
+                    (Some(_), Some(_)) => Change::Modified,
+                    (Some(_), None) => Change::Removed,
+                    (None, _) => Change::Added,
+                };
+                ColumnDiff {
+                    change,
+                    before,
+                    after,
+                }
+            })
+            .collect();
+
+        // rows matched by key that only changed position keep the tables identical
+        let is_same_row = |row: &RowDiff| match row.change {
+            Change::Unchanged => true,
+            Change::Moved => {
+                map.is_keyed
+                    && row
+                        .cells
+                        .iter()
+                        .all(|cell| cell.change == CellChange::Unchanged)
+            }
+            _ => false,
+        };
+        let status = match (&before_table, &after_table) {
+            (Some(_), None) => TableStatus::Removed,
+            (None, Some(_)) => TableStatus::Added,
+            _ if rows.iter().all(is_same_row)
+                && columns.iter().all(|c| c.change == Change::Unchanged) =>
+            {
+                TableStatus::Identical
+            }
+            _ => TableStatus::Changed,
+        };
+
+        TableDiff {
+            name,
+            before_table,
+            after_table,
+            status,
+            columns,
+            rows,
+        }
+    }
+
+    /// Whether both tables hold the same columns and rows. Rows matched by key may be
+    /// stored in a different order; they are still listed as moved.
+    pub fn is_identical(&self) -> bool {
+        self.status == TableStatus::Identical
+    }
+
+    /// Column names in merged order, taken from the before header when present.
+    pub fn column_names(&self) -> Vec<String> {
+        self.header_names(|cell| cell.before.as_ref().or(cell.after.as_ref()))
+    }
+
+    /// Column names in merged order, taken from the after header when present.
+    /// Differs from `column_names` only for renamed columns.
+    pub fn after_column_names(&self) -> Vec<String> {
+        self.header_names(|cell| cell.after.as_ref().or(cell.before.as_ref()))
+    }
+
+    fn header_names(&self, pick: fn(&CellDiff) -> Option<&DatumWithType>) -> Vec<String> {
+        match self.rows.first() {
+            Some(header) => header
+                .cells
+                .iter()
+                .map(|cell| pick(cell).map(|name| name.to_string()).unwrap_or_default())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// `<input> - <table>` for each side, where `labels` name the before and after inputs,
+    /// or `missing` for the side without the table.
+    pub fn side_labels(&self, labels: (&str, &str), missing: &str) -> (String, String) {
+        let label = |input: &str, table: &Option<String>| match table {
+            Some(table) => format!("{} - {}", input, table),
+            None => missing.to_owned(),
+        };
+        (
+            label(labels.0, &self.before_table),
+            label(labels.1, &self.after_table),
+        )
+    }
+
+    /// Rows that were added, removed or modified, without the header.
+    pub fn changed_rows(&self) -> impl Iterator<Item = &RowDiff> {
+        self.rows
+            .iter()
+            .skip(1)
+            .filter(|row| row.change != Change::Unchanged)
+    }
+}
+
+pub(crate) fn diff_cell(
+    before: Option<&DatumWithType>,
+    after: Option<&DatumWithType>,
+    strategy: Strategy,
+) -> CellDiff {
+    let (change, segments) = match (before, after) {
+        (Some(b), Some(a)) if b == a => (CellChange::Unchanged, vec![]),
+        (Some(b), Some(a)) if b.datum_type == Type::Text && a.datum_type == Type::Text => (
+            CellChange::Modified,
+            str_lcs(
+                &String::from_utf8_lossy(&b.datum),
+                &String::from_utf8_lossy(&a.datum),
+                &strategy,
+            ),
+        ),
+        (Some(_), Some(_)) => (CellChange::Modified, vec![]),
+        (Some(_), None) => (CellChange::Removed, vec![]),
+        (None, Some(_)) => (CellChange::Added, vec![]),
+        (None, None) => (CellChange::Absent, vec![]),
+    };
+    CellDiff {
+        change,
+        before: before.cloned(),
+        after: after.cloned(),
+        segments,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{table_diff, text};
+
+    #[test]
+    fn cells_carry_values_and_segments() {
+        let diff = table_diff(
+            &["id,name", "1,old sword", "2,bow"],
+            &["id,name", "1,new sword", "3,axe"],
+            &["id"],
+        );
+
+        assert_eq!(diff.status, TableStatus::Changed);
+        let changes: Vec<Change> = diff.changed_rows().map(|row| row.change).collect();
+        assert_eq!(
+            changes,
+            vec![Change::Modified, Change::Removed, Change::Added]
+        );
+
+        let cell = &diff.rows[1].cells[1];
+        assert_eq!(cell.change, CellChange::Modified);
+        assert_eq!(cell.before, Some(text("old sword")));
+        assert_eq!(
+            cell.segments,
+            vec![
+                LcsStrResult::Added("new".to_owned()),
+                LcsStrResult::Deleted("old".to_owned()),
+                LcsStrResult::Both(" sword".to_owned()),
+            ]
+        );
+        assert_eq!(diff.columns[1].change, Change::Modified);
+        assert_eq!(diff.columns[0].change, Change::Unchanged);
+
+        let mut added = diff.clone();
+        added.before_table = None;
+        assert_eq!(
+            added.side_labels(("a.db", "b.db"), "/dev/null"),
+            ("/dev/null".to_owned(), "b.db - t".to_owned())
+        );
+    }
+
+    #[test]
+    fn rows_reordered_by_key_are_identical() {
+        let before = ["id,name", "1,a", "2,b", "3,c"];
+        let after = ["id,name", "3,c", "1,a", "2,b"];
+
+        let keyed = table_diff(&before, &after, &["id"]);
+        assert!(keyed.is_identical());
+        assert_eq!(keyed.changed_rows().next().unwrap().change, Change::Moved);
+
+        assert_eq!(
+            table_diff(&before, &after, &[]).status,
+            TableStatus::Changed
+        );
+    }
+}
@@ -0,0 +1,528 @@
+use crate::source::{open_source, Format, SourceOptions, TableSource};
+use crate::sqlite::{
+    get_foreign_keys, get_indexes, get_objects, get_primary_key, get_sql, get_table_header,
+    get_tables, ColumnInfo, ForeignKeyInfo, IndexInfo, SqliteSource,
+};
+use crate::tables::{pair_source_tables, pair_tables, TableSelection};
+use anyhow::Result;
+use rusqlite::Connection;
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ObjectKind {
+    Table,
+    Column,
+    PrimaryKey,
+    ForeignKey,
+    Index,
+    Trigger,
+    View,
+}
+
+impl ObjectKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            ObjectKind::Table => "table",
+            ObjectKind::Column => "column",
+            ObjectKind::PrimaryKey => "primary key",
+            ObjectKind::ForeignKey => "foreign key",
+            ObjectKind::Index => "index",
+            ObjectKind::Trigger => "trigger",
+            ObjectKind::View => "view",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SchemaChange {
+    Added,
+    Removed,
+    /// Same definition under a new name; `before` and `after` hold the names.
+    Renamed,
+    Modified,
+}
+
+impl SchemaChange {
+    pub fn label(self) -> &'static str {
+        match self {
+            SchemaChange::Added => "added",
+            SchemaChange::Removed => "removed",
+            SchemaChange::Renamed => "renamed",
+            SchemaChange::Modified => "modified",
+        }
+    }
+}
+
+/// One structural difference between the two inputs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaDiff {
+    /// Table the object belongs to, named as in after when present there; the view itself for views.
+    pub table: String,
+    pub kind: ObjectKind,
+    /// `None` for tables, views, primary keys and foreign keys.
+    pub name: Option<String>,
+    pub change: SchemaChange,
+    /// Definition of the object on each side.
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// Compares the structure of two inputs. SQLite databases are compared in full: column
+/// types, NOT NULL, defaults, keys, indexes, triggers and views. Other formats only have
+/// table and column names to compare.
+pub fn diff_schemas(
+    before: &Path,
+    after: &Path,
+    options: &SourceOptions,
+    selection: &TableSelection,
+) -> Result<Vec<SchemaDiff>> {
+    if options.format_of(before) == Format::Sqlite && options.format_of(after) == Format::Sqlite {
+        let before = SqliteSource::open(before)?;
+        let after = SqliteSource::open(after)?;
+        return diff_sqlite_schemas(before.connection(), after.connection(), selection);
+    }
+
+    let before = open_source(before, options)?;
+    let after = open_source(after, options)?;
+    diff_headers(before.as_ref(), after.as_ref(), selection)
+}
+
+/// Compares table names, column names and primary keys of any two sources.
+pub fn diff_headers(
+    before: &dyn TableSource,
+    after: &dyn TableSource,
+    selection: &TableSelection,
+) -> Result<Vec<SchemaDiff>> {
+    let tables1 = before.tables()?;
+    let tables2 = after.tables()?;
+
+    let mut result = vec![];
+    for (table1, table2) in pair_source_tables(before, &tables1, after, &tables2, selection)? {
+        let table = table2.or(table1).unwrap();
+        match (table1, table2) {
+            (Some(table1), Some(table2)) => {
+                let columns = |names: Vec<String>| {
+                    names
+                        .into_iter()
+                        .map(|name| Column {
+                            name,
+                            definition: String::new(),
+                        })
+                        .collect::<Vec<_>>()
+                };
+                diff_columns(
+                    table,
+                    &columns(before.header(table1)?),
+                    &columns(after.header(table2)?),
+                    &mut result,
+                );
+                diff_primary_keys(
+                    table,
+                    &before.primary_key(table1)?,
+                    &after.primary_key(table2)?,
+                    &mut result,
+                );
+            }
+            (Some(_), None) => result.push(table_diff(table, SchemaChange::Removed, None)),
+            (None, Some(_)) => result.push(table_diff(table, SchemaChange::Added, None)),
+            (None, None) => unreachable!(),
+        }
+    }
+    Ok(result)
+}
+
+/// Compares tables, their columns, keys, indexes and triggers, and views of two databases.
+pub fn diff_sqlite_schemas(
+    before: &Connection,
+    after: &Connection,
+    selection: &TableSelection,
+) -> Result<Vec<SchemaDiff>> {
+    let tables1 = get_tables(before)?;
+    let tables2 = get_tables(after)?;
+    let triggers1 = get_objects(before, "trigger")?;
+    let triggers2 = get_objects(after, "trigger")?;
+
+    let mut result = vec![];
+    for (table1, table2) in pair_tables(&tables1, &tables2, selection)? {
+        let table = table2.or(table1).unwrap();
+        let (table1, table2) = match (table1, table2) {
+            (Some(table1), Some(table2)) => (table1, table2),
+            (Some(table1), None) => {
+                let sql = get_sql(before, table1)?.map(|sql| normalize_sql(&sql));
+                result.push(table_diff(table, SchemaChange::Removed, sql));
+                continue;
+            }
+            (None, Some(table2)) => {
+                let sql = get_sql(after, table2)?.map(|sql| normalize_sql(&sql));
+                result.push(table_diff(table, SchemaChange::Added, sql));
+                continue;
+            }
+            (None, None) => unreachable!(),
+        };
+        let first = result.len();
+
+        let columns = |conn: &Connection, table: &str| -> Result<Vec<Column>> {
+            Ok(get_table_header(conn, table)?
+                .iter()
+                .map(|column| Column {
+                    name: column.name.clone(),
+                    definition: column_definition(column),
+                })
+                .collect())
+        };
+        diff_columns(
+            table,
+            &columns(before, table1)?,
+            &columns(after, table2)?,
+            &mut result,
+        );
+
+        let key_names = |key: Vec<ColumnInfo>| key.into_iter().map(|c| c.name).collect::<Vec<_>>();
+        diff_primary_keys(
+            table,
+            &key_names(get_primary_key(before, table1)?),
+            &key_names(get_primary_key(after, table2)?),
+            &mut result,
+        );
+
+        let foreign_keys =
+            |conn: &Connection, table: &str| -> Result<Vec<(Option<String>, String)>> {
+                Ok(get_foreign_keys(conn, table)?
+                    .iter()
+                    .map(|key| (None, foreign_key_definition(key)))
+                    .collect())
+            };
+        diff_objects(
+            table,
+            ObjectKind::ForeignKey,
+            &foreign_keys(before, table1)?,
+            &foreign_keys(after, table2)?,
+            &mut result,
+        );
+
+        let indexes = |conn: &Connection, table: &str| -> Result<Vec<(Option<String>, String)>> {
+            let mut result = vec![];
+            // indexes of a PRIMARY KEY are reported as primary key changes
+            for index in get_indexes(conn, table)?
+                .into_iter()
+                .filter(|index| index.origin != "pk")
+            {
+                // automatic indexes are numbered by position, so they are matched by definition
+                let name =
+                    (!index.name.starts_with("sqlite_autoindex_")).then(|| index.name.clone());
+                result.push((name, index_definition(conn, &index)?));
+            }
+            Ok(result)
+        };
+        diff_objects(
+            table,
+            ObjectKind::Index,
+            &indexes(before, table1)?,
+            &indexes(after, table2)?,
+            &mut result,
+        );
+
+        let triggers = |conn: &Connection,
+                        triggers: &[(String, String)],
+                        table: &str|
+         -> Result<Vec<(Option<String>, String)>> {
+            let mut result = vec![];
+            for (name, _) in triggers.iter().filter(|(_, t)| t == table) {
+                let sql = get_sql(conn, name)?.unwrap_or_default();
+                result.push((Some(name.clone()), normalize_sql(&sql)));
+            }
+            Ok(result)
+        };
+        diff_objects(
+            table,
+            ObjectKind::Trigger,
+            &triggers(before, &triggers1, table1)?,
+            &triggers(after, &triggers2, table2)?,
+            &mut result,
+        );
+
+        // CHECK, UNIQUE and COLLATE changes only show in the table definition
+        let sql1 = get_sql(before, table1)?.map(|sql| normalize_sql(&sql));
+        let sql2 = get_sql(after, table2)?.map(|sql| normalize_sql(&sql));
+        if result.len() == first && sql1 != sql2 && table1 == table2 {
+            result.push(SchemaDiff {
+                table: table.clone(),
+                kind: ObjectKind::Table,
+                name: None,
+                change: SchemaChange::Modified,
+                before: sql1,
+                after: sql2,
+            });
+        }
+    }
+
+    let views = |conn: &Connection| -> Result<Vec<(Option<String>, String)>> {
+        let mut result = vec![];
+        for (name, _) in get_objects(conn, "view")?
+            .into_iter()
+            .filter(|(name, _)| selection.is_selected(&[name]))
+        {
+            let sql = get_sql(conn, &name)?.unwrap_or_default();
+            result.push((Some(name), normalize_sql(&sql)));
+        }
+        Ok(result)
+    };
+    let views1 = views(before)?;
+    let views2 = views(after)?;
+    let mut view_diffs = vec![];
+    diff_objects("", ObjectKind::View, &views1, &views2, &mut view_diffs);
+    result.extend(view_diffs.into_iter().map(|view| SchemaDiff {
+        table: view.name.unwrap(),
+        name: None,
+        ..view
+    }));
+    Ok(result)
+}
+
+struct Column {
+    name: String,
+    definition: String,
+}
+
+/// Pairs columns by name. A removed and an added column at the same position with the
+/// same definition are reported as a rename.
+fn diff_columns(table: &str, before: &[Column], after: &[Column], result: &mut Vec<SchemaDiff>) {
+    let position = |columns: &[Column], name: &str| columns.iter().position(|c| c.name == name);
+    let mut renamed_from = vec![None; after.len()];
+    for (i, column) in before.iter().enumerate() {
+        if position(after, &column.name).is_some() {
+            continue;
+        }
+        if let Some(added) = after.get(i) {
+            if position(before, &added.name).is_none() && added.definition == column.definition {
+                renamed_from[i] = Some(column);
+                continue;
+            }
+        }
+        result.push(SchemaDiff {
+            table: table.to_owned(),
+            kind: ObjectKind::Column,
+            name: Some(column.name.clone()),
+            change: SchemaChange::Removed,
+            before: non_empty(&column.definition),
+            after: None,
+        });
+    }
+
+    for (column, renamed_from) in after.iter().zip(renamed_from) {
+        let (change, before, after) = match (renamed_from, position(before, &column.name)) {
+            (Some(old), _) => (
+                SchemaChange::Renamed,
+                Some(old.name.clone()),
+                Some(column.name.clone()),
+            ),
+            (None, Some(j)) if before[j].definition != column.definition => (
+                SchemaChange::Modified,
+                Some(before[j].definition.clone()),
+                Some(column.definition.clone()),
+            ),
+            (None, Some(_)) => continue,
+            (None, None) => (SchemaChange::Added, None, non_empty(&column.definition)),
+        };
+        result.push(SchemaDiff {
+            table: table.to_owned(),
+            kind: ObjectKind::Column,
+            name: Some(column.name.clone()),
+            change,
+            before,
+            after,
+        });
+    }
+}
+
+fn diff_primary_keys(
+    table: &str,
+    before: &[String],
+    after: &[String],
+    result: &mut Vec<SchemaDiff>,
+) {
+    if before == after {
+        return;
+    }
+    let describe = |key: &[String]| (!key.is_empty()).then(|| format!("({})", key.join(", ")));
+    let change = match (before.is_empty(), after.is_empty()) {
+        (true, _) => SchemaChange::Added,
+        (_, true) => SchemaChange::Removed,
+        _ => SchemaChange::Modified,
+    };
+    result.push(SchemaDiff {
+        table: table.to_owned(),
+        kind: ObjectKind::PrimaryKey,
+        name: None,
+        change,
+        before: describe(before),
+        after: describe(after),
+    });
+}
+
+/// Pairs `(name, definition)` objects by name, or by definition when unnamed.
+fn diff_objects(
+    table: &str,
+    kind: ObjectKind,
+    before: &[(Option<String>, String)],
+    after: &[(Option<String>, String)],
+    result: &mut Vec<SchemaDiff>,
+) {
+    let find = |objects: &[(Option<String>, String)],
+                (name, definition): &(Option<String>, String)| {
+        objects
+            .iter()
+            .find(|(n, d)| n == name && (name.is_some() || d == definition))
+            .map(|(_, d)| d.clone())
+    };
+    let push = |result: &mut Vec<SchemaDiff>, name: &Option<String>, change, before, after| {
+        result.push(SchemaDiff {
+            table: table.to_owned(),
+            kind,
+            name: name.clone(),
+            change,
+            before,
+            after,
+        })
+    };
+
+    for object in before {
+        if find(after, object).is_none() {
+            push(
+                result,
+                &object.0,
+                SchemaChange::Removed,
+                Some(object.1.clone()),
+                None,
+            );
+        }
+    }
+    for object in after {
+        match find(before, object) {
+            None => push(
+                result,
+                &object.0,
+                SchemaChange::Added,
+                None,
+                Some(object.1.clone()),
+            ),
+            Some(definition) if definition != object.1 => push(
+                result,
+                &object.0,
+                SchemaChange::Modified,
+                Some(definition),
+                Some(object.1.clone()),
+            ),
+            Some(_) => {}
+        }
+    }
+}
+
+fn table_diff(table: &str, change: SchemaChange, sql: Option<String>) -> SchemaDiff {
+    let (before, after) = match change {
+        SchemaChange::Removed => (sql, None),
+        _ => (None, sql),
+    };
+    SchemaDiff {
+        table: table.to_owned(),
+        kind: ObjectKind::Table,
+        name: None,
+        change,
+        before,
+        after,
+    }
+}
+
+/// `TYPE NOT NULL DEFAULT value`, leaving out what is not declared.
+fn column_definition(column: &ColumnInfo) -> String {
+    let mut parts = vec![];
+    if !column.declared_type.is_empty() {
+        parts.push(column.declared_type.clone());
+    }
+    if column.not_null {
+        parts.push("NOT NULL".to_owned());
+    }
+    if let Some(default) = &column.default_value {
+        parts.push(format!("DEFAULT {}", default));
+    }
+    parts.join(" ")
+}
+
+fn foreign_key_definition(key: &ForeignKeyInfo) -> String {
+    let mut definition = format!("({}) REFERENCES {}", key.from.join(", "), key.table);
+    if !key.to.is_empty() {
+        definition += &format!(" ({})", key.to.join(", "));
+    }
+    for (action, value) in [("ON UPDATE", &key.on_update), ("ON DELETE", &key.on_delete)] {
+        if value != "NO ACTION" {
+            definition += &format!(" {} {}", action, value);
+        }
+    }
+    definition
+}
+
+fn index_definition(conn: &Connection, index: &IndexInfo) -> Result<String> {
+    Ok(match get_sql(conn, &index.name)? {
+        Some(sql) => normalize_sql(&sql),
+        None => format!(
+            "{}({})",
+            if index.unique { "UNIQUE " } else { "" },
+            index.columns.join(", ")
+        ),
+    })
+}
+
+/// Collapses whitespace so that reformatted statements compare equal.
+fn normalize_sql(sql: &str) -> String {
+    sql.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    (!text.is_empty()).then(|| text.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_column_key_and_index_changes() {
+        let before = Connection::open_in_memory().unwrap();
+        before
+            .execute_batch(
+                "CREATE TABLE item (id INTEGER PRIMARY KEY, title TEXT, hp INTEGER, gone BLOB);
+                 CREATE INDEX item_hp ON item (hp);
+                 CREATE VIEW strong AS SELECT * FROM item WHERE hp > 10;",
+            )
+            .unwrap();
+        let after = Connection::open_in_memory().unwrap();
+        after
+            .execute_batch(
+                "CREATE TABLE item (id INTEGER, name TEXT, hp REAL NOT NULL DEFAULT 0, mp INTEGER,
+                                    PRIMARY KEY (id, name));
+                 CREATE INDEX item_hp ON item (hp DESC);",
+            )
+            .unwrap();
+
+        let result = diff_sqlite_schemas(&before, &after, &TableSelection::default()).unwrap();
+        let summary: Vec<_> = result
+            .iter()
+            .map(|d| (d.kind, d.name.as_deref(), d.change))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (ObjectKind::Column, Some("gone"), SchemaChange::Removed),
+                (ObjectKind::Column, Some("name"), SchemaChange::Renamed),
+                (ObjectKind::Column, Some("hp"), SchemaChange::Modified),
+                (ObjectKind::Column, Some("mp"), SchemaChange::Added),
+                (ObjectKind::PrimaryKey, None, SchemaChange::Modified),
+                (ObjectKind::Index, Some("item_hp"), SchemaChange::Modified),
+                (ObjectKind::View, None, SchemaChange::Removed),
+            ]
+        );
+        assert_eq!(result[2].before.as_deref(), Some("INTEGER"));
+        assert_eq!(result[2].after.as_deref(), Some("REAL NOT NULL DEFAULT 0"));
+        assert_eq!(result[6].table, "strong");
+    }
+}
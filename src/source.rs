@@ -0,0 +1,129 @@
+use crate::csv::{CsvOptions, CsvSource};
+use crate::sqlite::{DatumWithType, SqliteSource, Type};
+use crate::xlsx::{XlsxOptions, XlsxSource};
+use anyhow::{bail, Result};
+use ndarray::Array2;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A file holding one or more tables to compare.
+pub trait TableSource {
+    /// Names of the tables in file order.
+    fn tables(&self) -> Result<Vec<String>>;
+
+    /// Cells of `table` with the column names at row 0.
+    fn read_table(&self, table: &str) -> Result<Array2<DatumWithType>>;
+
+    /// Column names of `table`.
+    fn header(&self, table: &str) -> Result<Vec<String>> {
+        let grid = self.read_table(table)?;
+        if grid.nrows() == 0 {
+            return Ok(vec![]);
+        }
+        Ok(grid.row(0).iter().map(|name| name.to_string()).collect())
+    }
+
+    /// Names of the primary key columns in key order. Empty when there is none.
+    fn primary_key(&self, _table: &str) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+
+    /// Whether any row of `table` is NULL in one of `columns`.
+    fn has_null(&self, table: &str, columns: &[String]) -> Result<bool> {
+        if columns.is_empty() {
+            return Ok(false);
+        }
+        let grid = self.read_table(table)?;
+        if grid.nrows() == 0 {
+            return Ok(false);
+        }
+        let columns: Vec<usize> = (0..grid.ncols())
+            .filter(|&j| columns.contains(&grid[[0, j]].to_string()))
+            .collect();
+        Ok(grid
+            .rows()
+            .into_iter()
+            .skip(1)
+            .any(|row| columns.iter().any(|&j| row[j].datum_type == Type::Null)))
+    }
+
+    /// Whether the file always holds exactly one table, named after the file rather than
+    /// by its author.
+    fn is_single_table(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Format {
+    Sqlite,
+    Csv,
+    Tsv,
+    Xlsx,
+}
+
+impl Format {
+    /// Guesses the format from the file extension. Anything unknown is treated as SQLite.
+    pub fn from_path(path: &Path) -> Format {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "csv" => Format::Csv,
+            "tsv" => Format::Tsv,
+            "xlsx" | "xlsm" | "xlsb" | "xls" | "ods" => Format::Xlsx,
+            _ => Format::Sqlite,
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Format> {
+        match s.to_ascii_lowercase().as_str() {
+            "sqlite" => Ok(Format::Sqlite),
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            "xlsx" => Ok(Format::Xlsx),
+            _ => bail!("unknown format `{}`, expected sqlite, csv, tsv or xlsx", s),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SourceOptions {
+    /// Overrides the format guessed from the file extension.
+    pub format: Option<Format>,
+    pub infer_types: bool,
+    /// 0-based row holding the column names in spreadsheets.
+    pub header_row: u32,
+}
+
+impl SourceOptions {
+    /// The format `path` is read as.
+    pub fn format_of(&self, path: &Path) -> Format {
+        self.format.unwrap_or_else(|| Format::from_path(path))
+    }
+}
+
+pub fn open_source(path: &Path, options: &SourceOptions) -> Result<Box<dyn TableSource>> {
+    let format = options.format_of(path);
+    Ok(match format {
+        Format::Sqlite => Box::new(SqliteSource::open(path)?),
+        Format::Csv | Format::Tsv => Box::new(CsvSource::new(
+            path,
+            CsvOptions {
+                delimiter: if format == Format::Tsv { b'\t' } else { b',' },
+                infer_types: options.infer_types,
+            },
+        )),
+        Format::Xlsx => Box::new(XlsxSource::new(
+            path,
+            XlsxOptions {
+                header_row: options.header_row,
+            },
+        )),
+    })
+}
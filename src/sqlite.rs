@@ -1,33 +1,200 @@
+use crate::source::TableSource;
 use ndarray::Array2;
-use rusqlite::{Connection, DatabaseName, Error};
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, DatabaseName, Error, OpenFlags};
+use std::cmp::Ordering;
+use std::path::Path;
+
+pub struct SqliteSource {
+    conn: Connection,
+}
+
+impl SqliteSource {
+    /// Opens an existing database read-only; a missing file is an error, never created.
+    pub fn open(path: &Path) -> Result<SqliteSource, Error> {
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        Ok(SqliteSource {
+            conn: Connection::open_with_flags(path, flags)?,
+        })
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+impl From<Connection> for SqliteSource {
+    fn from(conn: Connection) -> SqliteSource {
+        SqliteSource { conn }
+    }
+}
+
+impl TableSource for SqliteSource {
+    fn tables(&self) -> anyhow::Result<Vec<String>> {
+        Ok(get_tables(&self.conn)?)
+    }
+
+    fn read_table(&self, table: &str) -> anyhow::Result<Array2<DatumWithType>> {
+        Ok(read_table_as_2d_array(&self.conn, table)?)
+    }
+
+    fn header(&self, table: &str) -> anyhow::Result<Vec<String>> {
+        Ok(get_table_header(&self.conn, table)?
+            .into_iter()
+            .map(|column| column.name)
+            .collect())
+    }
+
+    fn primary_key(&self, table: &str) -> anyhow::Result<Vec<String>> {
+        Ok(get_primary_key(&self.conn, table)?
+            .into_iter()
+            .map(|column| column.name)
+            .collect())
+    }
+
+    fn has_null(&self, table: &str, columns: &[String]) -> anyhow::Result<bool> {
+        if columns.is_empty() {
+            return Ok(false);
+        }
+        let condition: Vec<String> = columns
+            .iter()
+            .map(|column| format!("{} IS NULL", quote_identifier(column)))
+            .collect();
+        let sql = format!(
+            "SELECT EXISTS (SELECT 1 FROM {} WHERE {})",
+            quote_identifier(table),
+            condition.join(" OR ")
+        );
+        Ok(self.conn.query_row(&sql, [], |row| row.get(0))?)
+    }
+}
 
 pub fn get_tables(conn: &Connection) -> Result<Vec<String>, Error> {
-    let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE type='table'")?;
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%'",
+    )?;
     let rows = stmt.query_map::<String, _, _>([], |row| row.get(0))?;
-    Ok(rows.map(|r| r.unwrap()).collect())
+    rows.collect()
 }
 
-fn get_table_header(conn: &Connection, table_name: &str) -> Result<Vec<ColumnInfo>, Error> {
+pub fn get_table_header(conn: &Connection, table_name: &str) -> Result<Vec<ColumnInfo>, Error> {
     let mut result = vec![];
     conn.pragma(Some(DatabaseName::Main), "table_info", table_name, |row| {
         result.push(ColumnInfo {
             name: row.get(1)?,
             order: row.get(0)?,
-            is_primary_key: row.get(5)?,
+            declared_type: row.get(2)?,
+            not_null: row.get(3)?,
+            default_value: row.get(4)?,
+            primary_key_order: row.get(5)?,
+        });
+        Ok(())
+    })?;
+    Ok(result)
+}
+
+/// Returns the primary key columns in key order.
+/// Empty when the table has no declared primary key.
+pub fn get_primary_key(conn: &Connection, table_name: &str) -> Result<Vec<ColumnInfo>, Error> {
+    let mut key: Vec<ColumnInfo> = get_table_header(conn, table_name)?
+        .into_iter()
+        .filter(|column| column.is_primary_key())
+        .collect();
+    key.sort_by_key(|column| column.primary_key_order);
+    Ok(key)
+}
+
+/// Returns the `CREATE` statement of a table, index, trigger or view.
+/// `None` for indexes created implicitly by UNIQUE and PRIMARY KEY constraints.
+pub fn get_sql(conn: &Connection, name: &str) -> Result<Option<String>, Error> {
+    conn.query_row(
+        "SELECT sql FROM sqlite_master WHERE name = ?1",
+        [name],
+        |row| row.get(0),
+    )
+}
+
+/// Returns the views or triggers (`kind` is `view` or `trigger`) as `(name, table)` pairs.
+pub fn get_objects(conn: &Connection, kind: &str) -> Result<Vec<(String, String)>, Error> {
+    let mut stmt =
+        conn.prepare("SELECT name, tbl_name FROM sqlite_master WHERE type = ?1 ORDER BY name")?;
+    let rows = stmt.query_map([kind], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+/// Returns the indexes of a table, including the ones backing UNIQUE and PRIMARY KEY constraints.
+pub fn get_indexes(conn: &Connection, table_name: &str) -> Result<Vec<IndexInfo>, Error> {
+    let mut result = vec![];
+    conn.pragma(Some(DatabaseName::Main), "index_list", table_name, |row| {
+        result.push(IndexInfo {
+            name: row.get(1)?,
+            unique: row.get(2)?,
+            origin: row.get(3)?,
+            columns: vec![],
         });
         Ok(())
     })?;
+    for index in &mut result {
+        conn.pragma(Some(DatabaseName::Main), "index_info", &index.name, |row| {
+            // expression columns have no name
+            let name: Option<String> = row.get(2)?;
+            index
+                .columns
+                .push(name.unwrap_or_else(|| "<expr>".to_owned()));
+            Ok(())
+        })?;
+    }
     Ok(result)
 }
 
+/// Returns the foreign keys of a table, one entry per constraint.
+pub fn get_foreign_keys(conn: &Connection, table_name: &str) -> Result<Vec<ForeignKeyInfo>, Error> {
+    let mut result: Vec<ForeignKeyInfo> = vec![];
+    conn.pragma(
+        Some(DatabaseName::Main),
+        "foreign_key_list",
+        table_name,
+        |row| {
+            let id: usize = row.get(0)?;
+            let from: String = row.get(3)?;
+            let to: Option<String> = row.get(4)?;
+            match result.iter_mut().find(|key| key.id == id) {
+                Some(key) => {
+                    key.from.push(from);
+                    key.to.extend(to);
+                }
+                None => result.push(ForeignKeyInfo {
+                    id,
+                    table: row.get(2)?,
+                    from: vec![from],
+                    to: to.into_iter().collect(),
+                    on_update: row.get(5)?,
+                    on_delete: row.get(6)?,
+                }),
+            }
+            Ok(())
+        },
+    )?;
+    Ok(result)
+}
+
+/// Quotes an identifier so that names with spaces, keywords or quotes can be used in SQL.
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
 pub fn read_table_as_2d_array(
     conn: &Connection,
     table_name: &str,
 ) -> Result<Array2<DatumWithType>, Error> {
     let header = get_table_header(conn, table_name)?;
-    let row_len = conn.query_row(&format!("SELECT COUNT(*) FROM {}", table_name), [], |row| {
-        row.get(0).map(|v: usize| v + 1) // + 1 for header
-    })?;
+    let row_len = conn.query_row(
+        &format!("SELECT COUNT(*) FROM {}", quote_identifier(table_name)),
+        [],
+        |row| {
+            row.get(0).map(|v: usize| v + 1) // + 1 for header
+        },
+    )?;
     let column_len = header.len();
 
     let mut result: Array2<DatumWithType> =
@@ -39,53 +206,68 @@ pub fn read_table_as_2d_array(
         };
     }
 
-    let mut stmt = conn.prepare(&format!("SELECT * FROM {}", table_name))?;
+    let mut stmt = conn.prepare(&format!("SELECT * FROM {}", quote_identifier(table_name)))?;
     let mut rows = stmt.query([])?;
 
     let mut i = 1;
     while let Some(row) = rows.next()? {
         for j in 0..column_len {
-            let val = row.get_ref(j)?;
-            match val {
-                rusqlite::types::ValueRef::Null => {}
-                rusqlite::types::ValueRef::Integer(val) => {
-                    result[[i, j]] = DatumWithType {
-                        datum: val.to_le_bytes().to_vec(),
-                        datum_type: Type::Integer,
-                    };
-                }
-                rusqlite::types::ValueRef::Real(r) => {
-                    result[[i, j]] = DatumWithType {
-                        datum: r.to_le_bytes().to_vec(),
-                        datum_type: Type::Real,
-                    };
-                }
-                rusqlite::types::ValueRef::Text(s) => {
-                    result[[i, j]] = DatumWithType {
-                        datum: s.to_vec(),
-                        datum_type: Type::Text,
-                    };
-                }
-                rusqlite::types::ValueRef::Blob(b) => {
-                    result[[i, j]] = DatumWithType {
-                        datum: b.to_vec(),
-                        datum_type: Type::Blob,
-                    };
-                }
-            }
+            result[[i, j]] = to_datum(row.get_ref(j)?);
         }
         i += 1;
     }
     Ok(result)
 }
 
-struct ColumnInfo {
-    name: String,
-    order: usize,
-    is_primary_key: bool,
+/// Copies a value read from SQLite, keeping its storage class.
+pub fn to_datum(value: ValueRef) -> DatumWithType {
+    let (datum, datum_type) = match value {
+        ValueRef::Null => (vec![], Type::Null),
+        ValueRef::Integer(val) => (val.to_le_bytes().to_vec(), Type::Integer),
+        ValueRef::Real(r) => (r.to_le_bytes().to_vec(), Type::Real),
+        ValueRef::Text(s) => (s.to_vec(), Type::Text),
+        ValueRef::Blob(b) => (b.to_vec(), Type::Blob),
+    };
+    DatumWithType { datum, datum_type }
+}
+
+pub struct ColumnInfo {
+    pub name: String,
+    pub order: usize,
+    /// Type as written in `CREATE TABLE`, empty when none was given
+    pub declared_type: String,
+    pub not_null: bool,
+    /// Default value as an SQL expression
+    pub default_value: Option<String>,
+    /// 1-based position within the primary key, 0 when the column is not part of it
+    pub primary_key_order: usize,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+impl ColumnInfo {
+    pub fn is_primary_key(&self) -> bool {
+        self.primary_key_order > 0
+    }
+}
+
+pub struct IndexInfo {
+    pub name: String,
+    pub unique: bool,
+    /// `c` for `CREATE INDEX`, `u` for UNIQUE and `pk` for PRIMARY KEY constraints
+    pub origin: String,
+    pub columns: Vec<String>,
+}
+
+pub struct ForeignKeyInfo {
+    pub id: usize,
+    pub table: String,
+    pub from: Vec<String>,
+    /// Empty when the key refers to the primary key of `table`
+    pub to: Vec<String>,
+    pub on_update: String,
+    pub on_delete: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct DatumWithType {
     pub datum: Vec<u8>,
     pub datum_type: Type,
@@ -100,7 +282,78 @@ impl Default for DatumWithType {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+impl std::fmt::Display for DatumWithType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.datum_type {
+            Type::Null => write!(f, "NULL"),
+            Type::Integer => write!(
+                f,
+                "{}",
+                i64::from_le_bytes(self.datum.as_slice().try_into().unwrap())
+            ),
+            Type::Real => write!(
+                f,
+                "{}",
+                f64::from_le_bytes(self.datum.as_slice().try_into().unwrap())
+            ),
+            Type::Text => write!(f, "{}", String::from_utf8_lossy(&self.datum)),
+            Type::Blob => write!(f, "<blob {} bytes>", self.datum.len()),
+        }
+    }
+}
+
+/// Orders like SQLite: NULL, then numbers by value, then text and blobs bytewise.
+impl Ord for DatumWithType {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let rank = |d: &DatumWithType| match d.datum_type {
+            Type::Null => 0,
+            Type::Integer | Type::Real => 1,
+            Type::Text => 2,
+            Type::Blob => 3,
+        };
+        let integer =
+            |d: &DatumWithType| i64::from_le_bytes(d.datum.as_slice().try_into().unwrap());
+        let real = |d: &DatumWithType| f64::from_le_bytes(d.datum.as_slice().try_into().unwrap());
+        rank(self)
+            .cmp(&rank(other))
+            .then_with(|| match (self.datum_type, other.datum_type) {
+                (Type::Integer, Type::Integer) => integer(self).cmp(&integer(other)),
+                (Type::Real, Type::Real) => real(self).total_cmp(&real(other)),
+                (Type::Integer, Type::Real) => compare_integer_real(integer(self), real(other)),
+                (Type::Real, Type::Integer) => {
+                    compare_integer_real(integer(other), real(self)).reverse()
+                }
+                _ => Ordering::Equal,
+            })
+            // keeps the order consistent with Eq, e.g. for 1 and 1.0
+            .then_with(|| (self.datum_type as u8).cmp(&(other.datum_type as u8)))
+            .then_with(|| self.datum.cmp(&other.datum))
+    }
+}
+
+/// Compares an integer with a real exactly, without rounding the integer to a real.
+fn compare_integer_real(integer: i64, real: f64) -> Ordering {
+    // 2^63, the first real above every i64
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+    if real.is_nan() || real < -LIMIT {
+        Ordering::Greater
+    } else if real >= LIMIT {
+        Ordering::Less
+    } else {
+        let whole = real.trunc();
+        integer
+            .cmp(&(whole as i64))
+            .then_with(|| 0.0.partial_cmp(&(real - whole)).unwrap())
+    }
+}
+
+impl PartialOrd for DatumWithType {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
 pub enum Type {
     Null,
     Integer,
@@ -0,0 +1,411 @@
+//! Compares SQLite tables without loading them into memory.
+//!
+//! Both tables are read through cursors ordered by the key and merged like two sorted lists,
+//! so only the current row of each side is held at a time and changed rows are reported as
+//! soon as they are found.
+
+use crate::align::{compare, AlignOptions, IndexMap};
+use crate::model::{diff_cell, CellDiff, Change, RowDiff, TableDiff};
+use crate::source::TableSource;
+use crate::sqlite::{
+    get_table_header, quote_identifier, to_datum, DatumWithType, SqliteSource, Type,
+};
+use crate::tables::pair_tables;
+use crate::{check_key, diff_table, table_key, DiffOptions};
+use anyhow::{bail, Context, Result};
+use ndarray::Array2;
+use rusqlite::{Connection, Rows};
+use std::cmp::Ordering;
+
+/// Receives the differences of each table as they are found.
+pub trait ChangeSink {
+    /// Called first for every table pair. Only the header row of `table` is guaranteed to
+    /// be present; the changed rows follow through `row`.
+    fn table(&mut self, table: &TableDiff) -> Result<()>;
+    /// Called for every added, removed or modified row.
+    fn row(&mut self, row: &RowDiff) -> Result<()>;
+    /// Called last for every table pair.
+    fn finish(&mut self, identical: bool) -> Result<()>;
+}
+
+/// Compares every selected table of two databases. Tables with a key are streamed in key
+/// order; tables without one cannot be merged by key and are loaded and compared as usual.
+/// Returns whether every table is identical.
+pub fn stream_sources(
+    before: &SqliteSource,
+    after: &SqliteSource,
+    options: &DiffOptions,
+    sink: &mut impl ChangeSink,
+) -> Result<bool> {
+    let tables1 = before.tables()?;
+    let tables2 = after.tables()?;
+
+    let pairs = pair_tables(&tables1, &tables2, &options.selection)?;
+    check_key(before, after, &pairs, &options.key)?;
+
+    let mut identical = true;
+    for (table1, table2) in pairs {
+        let name = table1.or(table2).unwrap();
+        let key = match (table1, table2) {
+            (Some(table1), Some(table2)) => table_key(before, table1, after, table2, options)?,
+            _ => vec![],
+        };
+
+        identical &= if table1.is_some() && table2.is_some() && key.is_empty() {
+            let diff = diff_table(before, table1, after, table2, options)?;
+            sink.table(&diff)?;
+            for row in diff.changed_rows() {
+                sink.row(row)?;
+            }
+            sink.finish(diff.is_identical())?;
+            diff.is_identical()
+        } else {
+            stream_table(
+                (before.connection(), table1.map(String::as_str)),
+                (after.connection(), table2.map(String::as_str)),
+                &key,
+                &options.align,
+                sink,
+            )
+            .with_context(|| format!("failed to compare table `{}`", name))?
+        };
+    }
+    Ok(identical)
+}
+
+/// Compares two tables row by row in the order of `key`, which must identify a row on both
+/// sides. A table missing on one side is passed as `None`, and every row of the other is
+/// reported as added or removed. Row indices count rows in key order, starting at 1.
+/// Returns whether the tables are identical.
+pub fn stream_table(
+    (before, before_table): (&Connection, Option<&str>),
+    (after, after_table): (&Connection, Option<&str>),
+    key: &[String],
+    options: &AlignOptions,
+    sink: &mut impl ChangeSink,
+) -> Result<bool> {
+    let before_header = header_grid(before, before_table)?;
+    let after_header = header_grid(after, after_table)?;
+    let map = match (before_table, after_table) {
+        (Some(_), Some(_)) => compare(&before_header, &after_header, &[], options)?,
+        (Some(_), None) => IndexMap::removed(&before_header),
+        (None, Some(_)) => IndexMap::added(&after_header),
+        (None, None) => unreachable!(),
+    };
+    let header = TableDiff::new(
+        before_table.or(after_table).unwrap().to_owned(),
+        before_table.map(str::to_owned),
+        after_table.map(str::to_owned),
+        &before_header,
+        &after_header,
+        &map,
+        options.strategy,
+    );
+    sink.table(&header)?;
+    let columns: Vec<(Option<usize>, Option<usize>)> = header
+        .columns
+        .iter()
+        .map(|column| (column.before, column.after))
+        .collect();
+
+    let mut before_statement = before_table
+        .map(|table| before.prepare(&ordered_select(table, key)))
+        .transpose()?;
+    let mut after_statement = after_table
+        .map(|table| after.prepare(&ordered_select(table, key)))
+        .transpose()?;
+    let mut before = Cursor::new(before_statement.as_mut(), &before_header, key, "before")?;
+    let mut after = Cursor::new(after_statement.as_mut(), &after_header, key, "after")?;
+
+    let mut identical = header.is_identical();
+    loop {
+        let order = match (&before.current, &after.current) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(b), Some(a)) => before.key(b).cmp(&after.key(a)),
+        };
+        let (before_row, after_row) = match order {
+            Ordering::Less => (before.current.as_ref(), None),
+            Ordering::Greater => (None, after.current.as_ref()),
+            Ordering::Equal => (before.current.as_ref(), after.current.as_ref()),
+        };
+
+        // matched rows differ when a shared value differs or a column exists on one side only
+        let is_unchanged = match (before_row, after_row) {
+            (Some(b), Some(a)) => columns.iter().all(|&column| match column {
+                (Some(jb), Some(ja)) => b[jb] == a[ja],
+                _ => false,
+            }),
+            _ => false,
+        };
+        if !is_unchanged {
+            identical = false;
+            let cells: Vec<CellDiff> = columns
+                .iter()
+                .map(|&(jb, ja)| {
+                    diff_cell(
+                        before_row.zip(jb).map(|(row, j)| &row[j]),
+                        after_row.zip(ja).map(|(row, j)| &row[j]),
+                        options.strategy,
+                    )
+                })
+                .collect();
+            let change = match (before_row, after_row) {
+                (Some(_), Some(_)) => Change::Modified,
+                (Some(_), None) => Change::Removed,
+                (None, _) => Change::Added,
+            };
+            sink.row(&RowDiff {
+                change,
+                before: before_row.map(|_| before.position),
+                after: after_row.map(|_| after.position),
+                cells,
+            })?;
+        }
+
+        if order != Ordering::Greater {
+            before.advance()?;
+        }
+        if order != Ordering::Less {
+            after.advance()?;
+        }
+    }
+
+    sink.finish(identical)?;
+    Ok(identical)
+}
+
+/// The current row of one side of a merge, read from a cursor ordered by key.
+struct Cursor<'s> {
+    rows: Option<Rows<'s>>,
+    width: usize,
+    key: Vec<usize>,
+    side: &'static str,
+    current: Option<Vec<DatumWithType>>,
+    /// 1-based index of `current` in key order.
+    position: usize,
+}
+
+impl<'s> Cursor<'s> {
+    fn new(
+        statement: Option<&'s mut rusqlite::Statement>,
+        header: &Array2<DatumWithType>,
+        key: &[String],
+        side: &'static str,
+    ) -> Result<Cursor<'s>> {
+        let Some(statement) = statement else {
+            return Ok(Cursor {
+                rows: None,
+                width: 0,
+                key: vec![],
+                side,
+                current: None,
+                position: 0,
+            });
+        };
+
+        let key = key
+            .iter()
+            .map(|name| {
+                header
+                    .row(0)
+                    .iter()
+                    .position(|h| h.datum == name.as_bytes())
+                    .with_context(|| format!("key column `{}` not found in {} table", name, side))
+            })
+            .collect::<Result<_>>()?;
+        let mut cursor = Cursor {
+            rows: Some(statement.query([])?),
+            width: header.ncols(),
+            key,
+            side,
+            current: None,
+            position: 0,
+        };
+        cursor.advance()?;
+        Ok(cursor)
+    }
+
+    fn key<'r>(&self, row: &'r [DatumWithType]) -> Vec<&'r DatumWithType> {
+        self.key.iter().map(|&j| &row[j]).collect()
+    }
+
+    /// Moves to the next row, checking that keys strictly increase.
+    fn advance(&mut self) -> Result<()> {
+        let next = match &mut self.rows {
+            Some(rows) => match rows.next()? {
+                Some(row) => Some(
+                    (0..self.width)
+                        .map(|j| row.get_ref(j).map(to_datum))
+                        .collect::<rusqlite::Result<Vec<_>>>()?,
+                ),
+                None => None,
+            },
+            None => None,
+        };
+
+        if let (Some(previous), Some(next)) = (&self.current, &next) {
+            if !self.key.is_empty() && self.key(next) <= self.key(previous) {
+                let values: Vec<String> = self.key(next).iter().map(|v| v.to_string()).collect();
+                bail!(
+                    "duplicate key ({}) in {} table at row {}",
+                    values.join(", "),
+                    self.side,
+                    self.position + 1
+                );
+            }
+        }
+        self.current = next;
+        self.position += 1;
+        Ok(())
+    }
+}
+
+/// A grid holding only the header row of `table`, or an empty grid when it is missing.
+fn header_grid(conn: &Connection, table: Option<&str>) -> Result<Array2<DatumWithType>> {
+    let Some(table) = table else {
+        return Ok(Array2::default((0, 0)));
+    };
+    let names: Vec<DatumWithType> = get_table_header(conn, table)?
+        .into_iter()
+        .map(|column| DatumWithType {
+            datum: column.name.into_bytes(),
+            datum_type: Type::Text,
+        })
+        .collect();
+    Ok(Array2::from_shape_vec((1, names.len()), names)?)
+}
+
+/// Selects every column of `table` ordered by `key`, comparing text bytewise like
+/// `DatumWithType` does regardless of the columns' collations.
+fn ordered_select(table: &str, key: &[String]) -> String {
+    let mut sql = format!("SELECT * FROM {}", quote_identifier(table));
+    if !key.is_empty() {
+        let terms: Vec<String> = key
+            .iter()
+            .map(|column| format!("{} COLLATE BINARY", quote_identifier(column)))
+            .collect();
+        sql.push_str(&format!(" ORDER BY {}", terms.join(", ")));
+    }
+    sql
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Collect {
+        rows: Vec<(Change, Option<usize>, Option<usize>)>,
+        identical: Vec<bool>,
+    }
+
+    impl ChangeSink for Collect {
+        fn table(&mut self, _: &TableDiff) -> Result<()> {
+            Ok(())
+        }
+
+        fn row(&mut self, row: &RowDiff) -> Result<()> {
+            self.rows.push((row.change, row.before, row.after));
+            Ok(())
+        }
+
+        fn finish(&mut self, identical: bool) -> Result<()> {
+            self.identical.push(identical);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn rows_are_merged_in_key_order() {
+        let before = Connection::open_in_memory().unwrap();
+        before
+            .execute_batch(
+                "CREATE TABLE item (id INTEGER PRIMARY KEY, name TEXT);
+                 INSERT INTO item VALUES (3, 'c'), (1, 'a'), (2, 'b');",
+            )
+            .unwrap();
+        let after = Connection::open_in_memory().unwrap();
+        after
+            .execute_batch(
+                "CREATE TABLE item (id INTEGER PRIMARY KEY, name TEXT);
+                 INSERT INTO item VALUES (4, 'd'), (1, 'a'), (3, 'C');",
+            )
+            .unwrap();
+
+        let mut sink = Collect::default();
+        let key = ["id".to_owned()];
+        let options = AlignOptions::default();
+        let identical = stream_table(
+            (&before, Some("item")),
+            (&after, Some("item")),
+            &key,
+            &options,
+            &mut sink,
+        )
+        .unwrap();
+
+        assert!(!identical);
+        assert_eq!(
+            sink.rows,
+            vec![
+                (Change::Removed, Some(2), None),
+                (Change::Modified, Some(3), Some(2)),
+                (Change::Added, None, Some(3)),
+            ]
+        );
+
+        before
+            .execute_batch("CREATE TABLE dup (k TEXT); INSERT INTO dup VALUES ('x'), ('x');")
+            .unwrap();
+        let error = stream_table(
+            (&before, Some("dup")),
+            (&before, Some("dup")),
+            &["k".to_owned()],
+            &options,
+            &mut sink,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("duplicate key (x)"));
+    }
+
+    #[test]
+    fn large_integer_keys_keep_their_order() {
+        // 2^60 + 1023 and 2^60 + 1024 round to the same f64
+        let before = Connection::open_in_memory().unwrap();
+        before
+            .execute_batch(
+                "CREATE TABLE item (id INTEGER PRIMARY KEY, name TEXT);
+                 INSERT INTO item VALUES (1152921504606847231, 'a'), (1152921504606847232, 'b');",
+            )
+            .unwrap();
+        let after = Connection::open_in_memory().unwrap();
+        after
+            .execute_batch(
+                "CREATE TABLE item (id INTEGER PRIMARY KEY, name TEXT);
+                 INSERT INTO item VALUES (1152921504606847232, 'B'), (1152921504606847233, 'c');",
+            )
+            .unwrap();
+
+        let mut sink = Collect::default();
+        let identical = stream_table(
+            (&before, Some("item")),
+            (&after, Some("item")),
+            &["id".to_owned()],
+            &AlignOptions::default(),
+            &mut sink,
+        )
+        .unwrap();
+
+        assert!(!identical);
+        assert_eq!(
+            sink.rows,
+            vec![
+                (Change::Removed, Some(1), None),
+                (Change::Modified, Some(2), Some(1)),
+                (Change::Added, None, Some(2)),
+            ]
+        );
+    }
+}
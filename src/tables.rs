@@ -0,0 +1,208 @@
+use crate::source::TableSource;
+use anyhow::{bail, Result};
+
+/// Which tables to compare and how tables are paired across the two inputs.
+#[derive(Default, Clone)]
+pub struct TableSelection {
+    /// Names or glob patterns (`*`, `?`) of tables to compare. Empty means every table.
+    pub include: Vec<String>,
+    /// Names or glob patterns of tables to skip.
+    pub exclude: Vec<String>,
+    /// `(before, after)` pairs of tables compared with each other despite different names.
+    pub rename: Vec<(String, String)>,
+}
+
+impl TableSelection {
+    pub(crate) fn is_selected(&self, names: &[&String]) -> bool {
+        let matches = |patterns: &[String]| {
+            patterns
+                .iter()
+                .any(|p| names.iter().any(|name| glob_match(p, name)))
+        };
+        (self.include.is_empty() || matches(&self.include)) && !matches(&self.exclude)
+    }
+}
+
+/// Pairs tables by name, or by `selection.rename`. Tables only in `tables2` come last.
+pub fn pair_tables<'a>(
+    tables1: &'a [String],
+    tables2: &'a [String],
+    selection: &TableSelection,
+) -> Result<Vec<(Option<&'a String>, Option<&'a String>)>> {
+    for (old, new) in &selection.rename {
+        if !tables1.contains(old) {
+            bail!("table `{}` not found in before", old);
+        }
+        if !tables2.contains(new) {
+            bail!("table `{}` not found in after", new);
+        }
+    }
+    // a mistyped name or pattern would otherwise compare nothing and report no differences
+    for pattern in &selection.include {
+        if !tables1
+            .iter()
+            .chain(tables2)
+            .any(|t| glob_match(pattern, t))
+        {
+            if is_pattern(pattern) {
+                bail!("no table matches `{}`", pattern);
+            }
+            bail!("table `{}` not found", pattern);
+        }
+    }
+
+    let renamed_to = |t1: &String| {
+        selection
+            .rename
+            .iter()
+            .find(|(old, _)| old == t1)
+            .map(|(_, new)| new)
+    };
+    let is_rename_target = |t2: &String| selection.rename.iter().any(|(_, new)| new == t2);
+
+    let mut result: Vec<_> = tables1
+        .iter()
+        .map(|t1| {
+            let t2 = match renamed_to(t1) {
+                Some(new) => tables2.iter().find(|t2| *t2 == new),
+                None => tables2.iter().find(|t2| *t2 == t1 && !is_rename_target(t2)),
+            };
+            (Some(t1), t2)
+        })
+        .collect();
+    let paired: Vec<&String> = result.iter().filter_map(|(_, t2)| *t2).collect();
+    result.extend(
+        tables2
+            .iter()
+            .filter(|t2| !paired.contains(t2))
+            .map(|t2| (None, Some(t2))),
+    );
+
+    result.retain(|(t1, t2)| {
+        let names: Vec<&String> = t1.iter().chain(t2.iter()).copied().collect();
+        selection.is_selected(&names)
+    });
+    Ok(result)
+}
+
+/// Pairs the tables of two sources like `pair_tables`. When both sources are single tables,
+/// such as two csv files, they are paired with each other whatever their names.
+pub fn pair_source_tables<'a>(
+    before: &dyn TableSource,
+    tables1: &'a [String],
+    after: &dyn TableSource,
+    tables2: &'a [String],
+    selection: &TableSelection,
+) -> Result<Vec<(Option<&'a String>, Option<&'a String>)>> {
+    match (tables1, tables2) {
+        ([table1], [table2])
+            if before.is_single_table()
+                && after.is_single_table()
+                && table1 != table2
+                && selection.rename.is_empty() =>
+        {
+            let selection = TableSelection {
+                rename: vec![(table1.clone(), table2.clone())],
+                ..selection.clone()
+            };
+            pair_tables(tables1, tables2, &selection)
+        }
+        _ => pair_tables(tables1, tables2, selection),
+    }
+}
+
+fn is_pattern(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// Matches `text` against a glob `pattern` where `*` matches any run of characters
+/// and `?` matches exactly one.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bt)) => {
+                    backtrack = Some((bp, bt + 1));
+                    p = bp + 1;
+                    t = bt + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv::{CsvOptions, CsvSource};
+    use std::path::Path;
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("item_*", "item_locale"));
+        assert!(glob_match("*_log", "battle_log"));
+        assert!(glob_match("a?c", "abc"));
+        assert!(!glob_match("a?c", "ac"));
+        assert!(!glob_match("item_*", "items"));
+    }
+
+    #[test]
+    fn renamed_tables_are_paired() {
+        let tables1 = vec!["a".to_owned(), "b".to_owned()];
+        let tables2 = vec!["b".to_owned(), "c".to_owned()];
+        let selection = TableSelection {
+            rename: vec![("a".to_owned(), "c".to_owned())],
+            exclude: vec!["b".to_owned()],
+            ..Default::default()
+        };
+        let pairs = pair_tables(&tables1, &tables2, &selection).unwrap();
+        assert_eq!(pairs, vec![(Some(&tables1[0]), Some(&tables2[1]))]);
+    }
+
+    #[test]
+    fn includes_matching_nothing_are_errors() {
+        let tables = vec!["item".to_owned()];
+        let include = |pattern: &str| TableSelection {
+            include: vec![pattern.to_owned()],
+            ..Default::default()
+        };
+        assert!(pair_tables(&tables, &tables, &include("it*")).is_ok());
+        let error = pair_tables(&tables, &tables, &include("zz*")).unwrap_err();
+        assert_eq!(error.to_string(), "no table matches `zz*`");
+        assert!(pair_tables(&tables, &tables, &include("items")).is_err());
+    }
+
+    #[test]
+    fn single_tables_are_paired_whatever_their_names() {
+        let options = || CsvOptions {
+            delimiter: b',',
+            infer_types: false,
+        };
+        let before = CsvSource::new(Path::new("old/items.csv"), options());
+        let after = CsvSource::new(Path::new("new/items_v2.csv"), options());
+        let (tables1, tables2) = (before.tables().unwrap(), after.tables().unwrap());
+        let selection = TableSelection::default();
+
+        let pairs = pair_source_tables(&before, &tables1, &after, &tables2, &selection).unwrap();
+        assert_eq!(pairs, vec![(Some(&tables1[0]), Some(&tables2[0]))]);
+        assert_eq!(
+            pair_tables(&tables1, &tables2, &selection).unwrap().len(),
+            2
+        );
+    }
+}
@@ -0,0 +1,362 @@
+use crate::lcs::LcsStrResult;
+use crate::model::{CellChange, CellDiff, Change, RowDiff, TableDiff};
+use crate::schema::{SchemaChange, SchemaDiff};
+use crate::sqlite::DatumWithType;
+use crate::stream::ChangeSink;
+use anyhow::Result;
+use std::io::{IsTerminal, Write};
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const BLUE: &str = "\x1b[34m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+/// Colors are used only when stdout is a terminal and `NO_COLOR` is unset or empty.
+pub fn use_color() -> bool {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    !no_color && std::io::stdout().is_terminal()
+}
+
+/// Writes changed rows and cells as text, one line per added or removed row and
+/// one line per modified cell. Without color, inline changes are marked
+/// `[-deleted-]` and `{+added+}`.
+pub fn write_text(
+    tables: &[TableDiff],
+    labels: (&str, &str),
+    mut writer: impl Write,
+    color: bool,
+) -> Result<()> {
+    let paint = Painter { color };
+    for table in tables {
+        write_table(&mut writer, &paint, table, labels)?;
+    }
+    Ok(())
+}
+
+/// Writes structural differences grouped by table, one line per changed object.
+pub fn write_schema(
+    schema: &[SchemaDiff],
+    labels: (&str, &str),
+    mut writer: impl Write,
+    color: bool,
+) -> Result<()> {
+    let paint = Painter { color };
+    if schema.is_empty() {
+        writeln!(writer, "{}", paint.paint(DIM, "schemas are identical"))?;
+        return Ok(());
+    }
+
+    writeln!(
+        writer,
+        "{}",
+        paint.paint(BOLD, &format!("--- {}", labels.0))
+    )?;
+    writeln!(
+        writer,
+        "{}",
+        paint.paint(BOLD, &format!("+++ {}", labels.1))
+    )?;
+    let mut table = None;
+    for diff in schema {
+        if table != Some(&diff.table) {
+            writeln!(writer, "{}", diff.table)?;
+            table = Some(&diff.table);
+        }
+
+        let object = match &diff.name {
+            Some(name) => format!("{} {}", diff.kind.label(), name),
+            None => diff.kind.label().to_owned(),
+        };
+        let definition =
+            |d: &Option<String>| d.as_ref().map(|d| format!(": {}", d)).unwrap_or_default();
+        let (style, line) = match diff.change {
+            SchemaChange::Added => (GREEN, format!("+ {}{}", object, definition(&diff.after))),
+            SchemaChange::Removed => (RED, format!("- {}{}", object, definition(&diff.before))),
+            SchemaChange::Renamed | SchemaChange::Modified => (
+                YELLOW,
+                format!(
+                    "~ {}: {} -> {}",
+                    object,
+                    diff.before.as_deref().unwrap_or("none"),
+                    diff.after.as_deref().unwrap_or("none")
+                ),
+            ),
+        };
+        writeln!(writer, "    {}", paint.paint(style, &line))?;
+    }
+    Ok(())
+}
+
+/// Writes text like `write_text` while changes are streamed. The header of a table is held
+/// back until its first changed row, so that identical tables still take a single line.
+pub struct TextStream<'l, W: Write> {
+    writer: W,
+    paint: Painter,
+    labels: (&'l str, &'l str),
+    table: Option<TableDiff>,
+    names: Vec<String>,
+    is_header_written: bool,
+}
+
+impl<'l, W: Write> TextStream<'l, W> {
+    pub fn new(writer: W, labels: (&'l str, &'l str), color: bool) -> TextStream<'l, W> {
+        TextStream {
+            writer,
+            paint: Painter { color },
+            labels,
+            table: None,
+            names: vec![],
+            is_header_written: false,
+        }
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        if let Some(table) = &self.table {
+            if !self.is_header_written {
+                write_table_header(&mut self.writer, &self.paint, table, self.labels)?;
+                self.is_header_written = true;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> ChangeSink for TextStream<'_, W> {
+    fn table(&mut self, table: &TableDiff) -> Result<()> {
+        self.names = table.column_names();
+        // only the header is needed to label the rows that follow
+        self.table = Some(TableDiff {
+            name: table.name.clone(),
+            before_table: table.before_table.clone(),
+            after_table: table.after_table.clone(),
+            status: table.status,
+            columns: table.columns.clone(),
+            rows: table.rows.iter().take(1).cloned().collect(),
+        });
+        self.is_header_written = false;
+        if !table.is_identical() {
+            self.write_header()?;
+        }
+        Ok(())
+    }
+
+    fn row(&mut self, row: &RowDiff) -> Result<()> {
+        self.write_header()?;
+        write_row(&mut self.writer, &self.paint, row, &self.names)
+    }
+
+    fn finish(&mut self, identical: bool) -> Result<()> {
+        if let Some(table) = self.table.take() {
+            if identical && !self.is_header_written {
+                write_identical(&mut self.writer, &self.paint, &table.name)?;
+            }
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+struct Painter {
+    color: bool,
+}
+
+impl Painter {
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color && !text.is_empty() {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_owned()
+        }
+    }
+}
+
+fn write_table(
+    writer: &mut impl Write,
+    paint: &Painter,
+    table: &TableDiff,
+    labels: (&str, &str),
+) -> Result<()> {
+    // rows that only moved by key are still listed
+    if table.is_identical() && table.changed_rows().next().is_none() {
+        return write_identical(writer, paint, &table.name);
+    }
+
+    write_table_header(writer, paint, table, labels)?;
+    let names = table.column_names();
+    for row in table.changed_rows() {
+        write_row(writer, paint, row, &names)?;
+    }
+    Ok(())
+}
+
+fn write_identical(writer: &mut impl Write, paint: &Painter, name: &str) -> Result<()> {
+    writeln!(
+        writer,
+        "{}",
+        paint.paint(DIM, &format!("{}: identical", name))
+    )?;
+    Ok(())
+}
+
+/// Writes the `---`/`+++` lines and the changed columns of `table`.
+fn write_table_header(
+    writer: &mut impl Write,
+    paint: &Painter,
+    table: &TableDiff,
+    labels: (&str, &str),
+) -> Result<()> {
+    // like diff(1), a missing side is shown as /dev/null
+    let (before_label, after_label) = table.side_labels(labels, "/dev/null");
+    writeln!(
+        writer,
+        "{}",
+        paint.paint(BOLD, &format!("--- {}", before_label))
+    )?;
+    writeln!(
+        writer,
+        "{}",
+        paint.paint(BOLD, &format!("+++ {}", after_label))
+    )?;
+
+    let names = table.column_names();
+    let after_names = table.after_column_names();
+    let is_table_changed = table.before_table.is_some() && table.after_table.is_some();
+    for ((column, name), after_name) in table.columns.iter().zip(&names).zip(&after_names) {
+        if !is_table_changed {
+            break;
+        }
+        match column.change {
+            Change::Added => writeln!(
+                writer,
+                "{}",
+                paint.paint(GREEN, &format!("+ column {}", name))
+            )?,
+            Change::Removed => writeln!(
+                writer,
+                "{}",
+                paint.paint(RED, &format!("- column {}", name))
+            )?,
+            Change::Renamed => writeln!(
+                writer,
+                "{}",
+                paint.paint(
+                    YELLOW,
+                    &format!("~ column {} renamed to {}", name, after_name)
+                )
+            )?,
+            Change::Moved => writeln!(
+                writer,
+                "{}",
+                paint.paint(BLUE, &format!("> column {} moved", name))
+            )?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn write_row(
+    writer: &mut impl Write,
+    paint: &Painter,
+    row: &RowDiff,
+    names: &[String],
+) -> Result<()> {
+    let values = |pick: fn(&CellDiff) -> Option<String>| {
+        row.cells
+            .iter()
+            .zip(names)
+            .filter_map(|(cell, name)| pick(cell).map(|value| format!("{}={}", name, value)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    match (row.change, row.before, row.after) {
+        (Change::Removed, Some(before), _) => {
+            let line = format!(
+                "- row {}: {}",
+                before,
+                values(|c| c.before.as_ref().map(|v| v.to_string()))
+            );
+            writeln!(writer, "{}", paint.paint(RED, &line))?;
+        }
+        (Change::Added, _, Some(after)) => {
+            let line = format!(
+                "+ row {}: {}",
+                after,
+                values(|c| c.after.as_ref().map(|v| v.to_string()))
+            );
+            writeln!(writer, "{}", paint.paint(GREEN, &line))?;
+        }
+        (Change::Moved, Some(before), Some(after)) => {
+            let line = format!("> row {} moved to {}", before, after);
+            writeln!(writer, "{}", paint.paint(BLUE, &line))?;
+            for (cell, name) in row.cells.iter().zip(names) {
+                if cell.change != CellChange::Unchanged {
+                    writeln!(writer, "    {}: {}", name, cell_text(paint, cell))?;
+                }
+            }
+        }
+        (_, Some(before), Some(after)) => {
+            let line = if before == after {
+                format!("~ row {}", before)
+            } else {
+                format!("~ row {} -> {}", before, after)
+            };
+            writeln!(writer, "{}", paint.paint(YELLOW, &line))?;
+            for (cell, name) in row.cells.iter().zip(names) {
+                if cell.change != CellChange::Unchanged {
+                    writeln!(writer, "    {}: {}", name, cell_text(paint, cell))?;
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn cell_text(paint: &Painter, cell: &CellDiff) -> String {
+    let value = |v: &Option<DatumWithType>| v.as_ref().map(|v| v.to_string()).unwrap_or_default();
+    if cell.segments.is_empty() {
+        return format!(
+            "{} -> {}",
+            paint.paint(RED, &value(&cell.before)),
+            paint.paint(GREEN, &value(&cell.after))
+        );
+    }
+
+    let (mut before, mut after) = (String::new(), String::new());
+    for segment in &cell.segments {
+        match segment {
+            LcsStrResult::Both(s) => {
+                before.push_str(s);
+                after.push_str(s);
+            }
+            LcsStrResult::Deleted(s) if paint.color => before.push_str(&paint.paint(RED, s)),
+            LcsStrResult::Deleted(s) => before.push_str(&format!("[-{}-]", s)),
+            LcsStrResult::Added(s) if paint.color => after.push_str(&paint.paint(GREEN, s)),
+            LcsStrResult::Added(s) => after.push_str(&format!("{{+{}+}}", s)),
+        }
+    }
+    format!("{} -> {}", before, after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::table_diff;
+
+    #[test]
+    fn inline_changes_are_marked_without_color() {
+        let diff = table_diff(&["id,name", "1,red bow"], &["id,name", "1,long bow"], &[]);
+
+        let mut output = vec![];
+        write_text(&[diff], ("a", "b"), &mut output, false).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(!output.contains('\x1b'));
+        assert!(output.contains("    name: [-red-] bow -> {+long+} bow"));
+    }
+}
@@ -0,0 +1,42 @@
+//! Fixtures shared by unit tests.
+
+use crate::align::{compare, AlignOptions};
+use crate::lcs::Strategy;
+use crate::model::TableDiff;
+use crate::sqlite::{DatumWithType, Type};
+use ndarray::Array2;
+
+pub fn text(s: &str) -> DatumWithType {
+    DatumWithType {
+        datum: s.as_bytes().to_vec(),
+        datum_type: Type::Text,
+    }
+}
+
+/// A grid of text cells from comma-separated rows, the header first.
+pub fn grid(rows: &[&str]) -> Array2<DatumWithType> {
+    let columns = rows[0].split(',').count();
+    let cells: Vec<_> = rows
+        .iter()
+        .flat_map(|row| row.split(','))
+        .map(text)
+        .collect();
+    Array2::from_shape_vec((rows.len(), columns), cells).unwrap()
+}
+
+/// The diff of a table `t` given as comma-separated rows on each side, aligned by `key`.
+pub fn table_diff(before: &[&str], after: &[&str], key: &[&str]) -> TableDiff {
+    let (before, after) = (grid(before), grid(after));
+    let key: Vec<String> = key.iter().map(|&column| column.to_owned()).collect();
+    let map = compare(&before, &after, &key, &AlignOptions::default()).unwrap();
+    let name = Some("t".to_owned());
+    TableDiff::new(
+        "t".to_owned(),
+        name.clone(),
+        name,
+        &before,
+        &after,
+        &map,
+        Strategy::Lcs,
+    )
+}
@@ -0,0 +1,368 @@
+use crate::lcs::LcsStrResult;
+use crate::model::{CellChange, Change, TableDiff, TableStatus};
+use crate::schema::{SchemaChange, SchemaDiff};
+use crate::sqlite::{DatumWithType, Type};
+use anyhow::Result;
+use rust_xlsxwriter::{Color, Format, FormatBorder, Url, Workbook, Worksheet};
+
+type RichString<'a> = Vec<(&'a Format, &'a str)>;
+
+/// Splits inline segments into the rich strings of the before and the after cell.
+fn rich_strings<'a>(
+    segments: &'a [LcsStrResult],
+    green: &'a Format,
+    red: &'a Format,
+    black: &'a Format,
+) -> (RichString<'a>, RichString<'a>) {
+    let mut result = (vec![], vec![]);
+
+    for segment in segments {
+        match segment {
+            LcsStrResult::Both(content) => {
+                result.0.push((black, content.as_str()));
+                result.1.push((black, content.as_str()));
+            }
+            LcsStrResult::Deleted(content) => {
+                result.0.push((red, content.as_str()));
+            }
+            LcsStrResult::Added(content) => {
+                result.1.push((green, content.as_str()));
+            }
+        }
+    }
+
+    result.0.retain(|(_, s)| !s.is_empty());
+    result.1.retain(|(_, s)| !s.is_empty());
+    result
+}
+
+/// Renders table diffs into a workbook: an index sheet linking to
+/// one side-by-side sheet per table, headed by [`TableDiff::side_labels`] of `labels`.
+pub fn write_workbook(tables: &[TableDiff], labels: (&str, &str)) -> Result<Workbook> {
+    let mut book = Workbook::new();
+    book.add_worksheet().set_name(INDEX_SHEET)?;
+    let mut sheet_names = vec![INDEX_SHEET.to_owned()];
+    let mut entries = vec![];
+
+    for table in tables {
+        let sheet_name = unique_sheet_name(&table.name, &mut sheet_names);
+        let sheet = book.add_worksheet();
+        sheet.set_name(&sheet_name)?;
+
+        let (before_label, after_label) = table.side_labels(labels, "");
+        write_table_diff(sheet, (&before_label, &after_label), table)?;
+        entries.push((table.name.clone(), table.status, sheet_name));
+    }
+
+    write_index_sheet(book.worksheet_from_index(0)?, &entries)?;
+    Ok(book)
+}
+
+/// Adds a sheet listing structural differences, one row per changed object.
+pub fn write_schema_sheet(book: &mut Workbook, schema: &[SchemaDiff]) -> Result<()> {
+    let mut sheet_names: Vec<String> = book.worksheets().iter().map(|sheet| sheet.name()).collect();
+    let sheet_name = unique_sheet_name(SCHEMA_SHEET, &mut sheet_names);
+    let sheet = book.add_worksheet();
+    sheet.set_name(&sheet_name)?;
+
+    let bold = Format::new().set_bold();
+    let headers = ["table", "object", "name", "change", "before", "after"];
+    let widths = [30, 12, 30, 12, 60, 60];
+    for (j, (header, width)) in headers.iter().zip(widths).enumerate() {
+        sheet.write_string_with_format(0, j as u16, *header, &bold)?;
+        sheet.set_column_width(j as u16, width)?;
+    }
+
+    for (i, diff) in schema.iter().enumerate() {
+        let row = i as u32 + 1;
+        let color = match diff.change {
+            SchemaChange::Added => Color::Green,
+            SchemaChange::Removed => Color::Red,
+            SchemaChange::Renamed | SchemaChange::Modified => Color::Yellow,
+        };
+        let format = Format::new().set_background_color(color);
+        sheet.write_string(row, 0, &diff.table)?;
+        sheet.write_string(row, 1, diff.kind.label())?;
+        sheet.write_string(row, 2, diff.name.as_deref().unwrap_or_default())?;
+        sheet.write_string_with_format(row, 3, diff.change.label(), &format)?;
+        sheet.write_string(row, 4, diff.before.as_deref().unwrap_or_default())?;
+        sheet.write_string(row, 5, diff.after.as_deref().unwrap_or_default())?;
+    }
+    Ok(())
+}
+
+/// Writes the side-by-side diff of one table into `sheet`:
+/// before on the left, after on the right, each under its label.
+pub fn write_table_diff(
+    sheet: &mut Worksheet,
+    labels: (&str, &str),
+    table: &TableDiff,
+) -> Result<()> {
+    let merged_column_len = table.columns.len();
+
+    let (i0, j0) = (1, 0);
+    let (i1, j1) = (i0, j0 + 1 + merged_column_len as u16);
+
+    {
+        let format = Format::new()
+            .set_background_color(Color::White)
+            .set_border(FormatBorder::None);
+
+        sheet.write_string_with_format(i0 - 1, j0, labels.0, &format)?;
+
+        sheet.write_string_with_format(i1 - 1, j1, labels.1, &format)?;
+    }
+
+    let black = Format::new().set_font_color(Color::Black);
+    let red = Format::new().set_font_color(Color::Red);
+    let green = Format::new().set_font_color(Color::Green);
+    let bg = Format::new().set_background_color(Color::Yellow);
+    let moved_from = Format::new().set_font_color(MOVED_FONT_COLOR);
+
+    for (i, row) in table.rows.iter().enumerate() {
+        let unchanged_color = match (row.change, row.before) {
+            (Change::Moved, Some(before)) => {
+                // The gap column between both sides names the row's position in the before
+                // table. Both sides are laid out in merged order, so that position is not a
+                // row of this sheet.
+                let text = format!("← before row {}", before);
+                sheet.write_string_with_format(i0 + i as u32, j1 - 1, &text, &moved_from)?;
+                MOVED_COLOR
+            }
+            _ => Color::White,
+        };
+        for (j, cell) in row.cells.iter().enumerate() {
+            let (before_row, before_column) = (i0 + i as u32, j0 + j as u16);
+            let (after_row, after_column) = (i1 + i as u32, j1 + j as u16);
+
+            let header_color = match table.columns[j].change {
+                Change::Renamed => Some(RENAMED_COLOR),
+                Change::Moved => Some(MOVED_COLOR),
+                _ => None,
+            };
+            if let (0, Some(color), Some(before), Some(after)) =
+                (i, header_color, &cell.before, &cell.after)
+            {
+                write_to_sheet(sheet, before_row, before_column, before, color)?;
+                write_to_sheet(sheet, after_row, after_column, after, color)?;
+                continue;
+            }
+
+            match (cell.change, &cell.before, &cell.after) {
+                (CellChange::Unchanged, Some(before), Some(after)) => {
+                    write_to_sheet(sheet, before_row, before_column, before, unchanged_color)?;
+                    write_to_sheet(sheet, after_row, after_column, after, unchanged_color)?;
+                }
+                (CellChange::Modified, Some(before), Some(after)) => {
+                    if cell.segments.is_empty() {
+                        write_to_sheet(sheet, before_row, before_column, before, Color::Yellow)?;
+                        write_to_sheet(sheet, after_row, after_column, after, Color::Yellow)?;
+                        continue;
+                    }
+
+                    let (rich_input_1, rich_input_2) =
+                        rich_strings(&cell.segments, &green, &red, &black);
+
+                    if !rich_input_1.is_empty() {
+                        sheet.write_rich_string_with_format(
+                            before_row,
+                            before_column,
+                            rich_input_1.as_slice(),
+                            &bg,
+                        )?;
+                    }
+
+                    if !rich_input_2.is_empty() {
+                        sheet.write_rich_string_with_format(
+                            after_row,
+                            after_column,
+                            rich_input_2.as_slice(),
+                            &bg,
+                        )?;
+                    }
+                }
+                (CellChange::Removed, Some(before), _) => {
+                    write_to_sheet(sheet, before_row, before_column, before, Color::Red)?;
+                    write_gray_blank(sheet, after_row, after_column)?;
+                }
+                (CellChange::Added, _, Some(after)) => {
+                    write_gray_blank(sheet, before_row, before_column)?;
+                    write_to_sheet(sheet, after_row, after_column, after, Color::Green)?;
+                }
+                _ => {
+                    // ex) row 삭제 column 추가면 여기로 타는게 가능
+                    //  + 가 추가, -가 삭제라 했을 때 아래와 같은 그림
+                    // 이 unified 그림에서 (1,1)은 before에도 after에도 없다.
+                    // 그래서 둘 다 gray blank로 그린다.
+                    // |  a  | + c |
+                    // | - b | + - |
+                    write_gray_blank(sheet, before_row, before_column)?;
+                    write_gray_blank(sheet, after_row, after_column)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+const INDEX_SHEET: &str = "Index";
+const MOVED_COLOR: Color = Color::RGB(0xBDD7EE);
+const MOVED_FONT_COLOR: Color = Color::RGB(0x1F4E79);
+const RENAMED_COLOR: Color = Color::RGB(0xF8CBAD);
+const SCHEMA_SHEET: &str = "Schema";
+
+fn status_color(status: TableStatus) -> Color {
+    match status {
+        TableStatus::Added => Color::Green,
+        TableStatus::Removed => Color::Red,
+        TableStatus::Changed => Color::Yellow,
+        TableStatus::Identical => Color::White,
+    }
+}
+
+/// Excel sheet names are at most 31 characters, may not contain `[]:*?/\`
+/// and must be unique ignoring case.
+fn unique_sheet_name(table_name: &str, used: &mut Vec<String>) -> String {
+    let base: String = table_name
+        .chars()
+        .map(|c| match c {
+            '[' | ']' | ':' | '*' | '?' | '/' | '\\' => '_',
+            c => c,
+        })
+        .collect();
+    let base = base.trim_matches('\'').to_owned();
+    let base = if base.is_empty() {
+        "table".to_owned()
+    } else {
+        base
+    };
+
+    let mut candidate: String = base.chars().take(31).collect();
+    let mut n = 1;
+    while used.iter().any(|u| u.eq_ignore_ascii_case(&candidate)) {
+        n += 1;
+        let suffix = format!("~{}", n);
+        candidate = base
+            .chars()
+            .take(31 - suffix.len())
+            .chain(suffix.chars())
+            .collect();
+    }
+    used.push(candidate.clone());
+    candidate
+}
+
+fn write_index_sheet(
+    sheet: &mut Worksheet,
+    entries: &[(String, TableStatus, String)],
+) -> Result<()> {
+    let bold = Format::new().set_bold();
+    sheet.write_string_with_format(0, 0, "table", &bold)?;
+    sheet.write_string_with_format(0, 1, "status", &bold)?;
+    sheet.set_column_width(0, 30)?;
+    sheet.set_column_width(1, 12)?;
+
+    for (i, (table, status, sheet_name)) in entries.iter().enumerate() {
+        let row = i as u32 + 1;
+        let link = format!("internal:'{}'!A1", sheet_name.replace('\'', "''"));
+        sheet.write_url_with_text(row, 0, Url::new(link), table)?;
+        let format = Format::new().set_background_color(status_color(*status));
+        sheet.write_string_with_format(row, 1, status.label(), &format)?;
+    }
+    Ok(())
+}
+
+fn write_gray_blank(sheet: &mut Worksheet, row: u32, column: u16) -> Result<()> {
+    let format = Format::new().set_background_color(Color::Gray);
+    sheet.write_blank(row, column, &format)?;
+    Ok(())
+}
+
+fn write_to_sheet(
+    sheet: &mut Worksheet,
+    row: u32,
+    column: u16,
+    value: &DatumWithType,
+    color: Color,
+) -> Result<()> {
+    let format = Format::new()
+        .set_background_color(color)
+        .set_border(FormatBorder::Thick);
+    match value {
+        DatumWithType {
+            datum,
+            datum_type: Type::Integer,
+        } => {
+            sheet.write_number_with_format(
+                row,
+                column,
+                i64::from_le_bytes(datum.as_slice().try_into()?) as f64,
+                &format,
+            )?;
+        }
+        DatumWithType {
+            datum,
+            datum_type: Type::Real,
+        } => {
+            sheet.write_number_with_format(
+                row,
+                column,
+                f64::from_le_bytes(datum.as_slice().try_into()?),
+                &format,
+            )?;
+        }
+        DatumWithType {
+            datum,
+            datum_type: Type::Text,
+        } => {
+            // SQLite does not enforce that text is valid UTF-8
+            sheet.write_string_with_format(
+                row,
+                column,
+                String::from_utf8_lossy(datum).as_ref(),
+                &format,
+            )?;
+        }
+        DatumWithType {
+            datum: _,
+            datum_type: Type::Blob,
+        } => {
+            sheet.write_string_with_format(row, column, "This is a Blob", &format)?;
+        }
+        DatumWithType {
+            datum: _,
+            datum_type: Type::Null,
+        } => {
+            sheet.write_string_with_format(row, column, "This is a DBNull", &format)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sheet_names_are_sanitized_and_unique() {
+        let mut used = vec!["Index".to_owned()];
+        assert_eq!(unique_sheet_name("index", &mut used), "index~2");
+        assert_eq!(unique_sheet_name("a/b:c", &mut used), "a_b_c");
+        let long = "x".repeat(40);
+        assert_eq!(unique_sheet_name(&long, &mut used), "x".repeat(31));
+        assert_eq!(
+            unique_sheet_name(&long, &mut used),
+            format!("{}~2", "x".repeat(29))
+        );
+    }
+
+    #[test]
+    fn invalid_utf8_text_is_written_lossily() {
+        let mut sheet = Worksheet::new();
+        let text = DatumWithType {
+            datum: vec![0xff, b'A'],
+            datum_type: Type::Text,
+        };
+        assert!(write_to_sheet(&mut sheet, 0, 0, &text, Color::White).is_ok());
+    }
+}
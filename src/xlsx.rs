@@ -0,0 +1,124 @@
+use crate::source::TableSource;
+use crate::sqlite::{DatumWithType, Type};
+use anyhow::{Context, Result};
+use calamine::{open_workbook_auto, Data, Reader};
+use ndarray::Array2;
+use std::path::{Path, PathBuf};
+
+pub struct XlsxOptions {
+    /// 0-based row holding the column names. Rows above it are ignored.
+    pub header_row: u32,
+}
+
+/// Every worksheet of a spreadsheet is a table.
+pub struct XlsxSource {
+    path: PathBuf,
+    options: XlsxOptions,
+}
+
+impl XlsxSource {
+    pub fn new(path: &Path, options: XlsxOptions) -> XlsxSource {
+        XlsxSource {
+            path: path.to_owned(),
+            options,
+        }
+    }
+}
+
+impl TableSource for XlsxSource {
+    fn tables(&self) -> Result<Vec<String>> {
+        get_sheets(&self.path)
+    }
+
+    fn read_table(&self, table: &str) -> Result<Array2<DatumWithType>> {
+        read_sheet_as_2d_array(&self.path, table, &self.options)
+    }
+}
+
+pub fn get_sheets(path: &Path) -> Result<Vec<String>> {
+    let workbook =
+        open_workbook_auto(path).with_context(|| format!("failed to open {}", path.display()))?;
+    Ok(workbook.sheet_names())
+}
+
+/// Reads a worksheet in the same shape as `read_table_as_2d_array`:
+/// the header row at row 0 followed by every row below it, down to the last used row.
+pub fn read_sheet_as_2d_array(
+    path: &Path,
+    sheet_name: &str,
+    options: &XlsxOptions,
+) -> Result<Array2<DatumWithType>> {
+    let mut workbook =
+        open_workbook_auto(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let range = workbook
+        .worksheet_range(sheet_name)
+        .with_context(|| format!("failed to read sheet `{}`", sheet_name))?;
+
+    let (start, end) = match (range.start(), range.end()) {
+        (Some(start), Some(end)) if end.0 >= options.header_row => (start, end),
+        _ => return Ok(Array2::default((0, 0))),
+    };
+    let first_row = options.header_row.max(start.0);
+    let row_len = (end.0 - options.header_row + 1) as usize;
+    let column_len = (end.1 - start.1 + 1) as usize;
+
+    let mut result: Array2<DatumWithType> =
+        Array2::from_shape_fn((row_len, column_len), |(_, _)| DatumWithType::default());
+    for row in first_row..=end.0 {
+        for column in start.1..=end.1 {
+            if let Some(cell) = range.get_value((row, column)) {
+                result[[
+                    (row - options.header_row) as usize,
+                    (column - start.1) as usize,
+                ]] = datum_from_cell(cell);
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Numbers without a fractional part become `Integer`, as spreadsheets store every number
+/// as a float. Booleans become `Integer` 0 or 1, the way SQLite stores them.
+fn datum_from_cell(cell: &Data) -> DatumWithType {
+    let integer = |i: i64| DatumWithType {
+        datum: i.to_le_bytes().to_vec(),
+        datum_type: Type::Integer,
+    };
+    let real = |r: f64| DatumWithType {
+        datum: r.to_le_bytes().to_vec(),
+        datum_type: Type::Real,
+    };
+    let text = |s: String| DatumWithType {
+        datum: s.into_bytes(),
+        datum_type: Type::Text,
+    };
+
+    match cell {
+        Data::Empty => DatumWithType::default(),
+        Data::Int(i) => integer(*i),
+        Data::Float(f) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => integer(*f as i64),
+        Data::Float(f) => real(*f),
+        Data::Bool(b) => integer(*b as i64),
+        Data::String(s) => text(s.clone()),
+        Data::DateTime(d) => real(d.as_f64()),
+        Data::DateTimeIso(s) | Data::DurationIso(s) => text(s.clone()),
+        Data::Error(e) => text(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_are_mapped_onto_types() {
+        assert_eq!(datum_from_cell(&Data::Float(3.0)).datum_type, Type::Integer);
+        assert_eq!(datum_from_cell(&Data::Float(3.5)).datum_type, Type::Real);
+        assert_eq!(datum_from_cell(&Data::Bool(true)).to_string(), "1");
+        assert_eq!(datum_from_cell(&Data::Empty), DatumWithType::default());
+        assert_eq!(
+            datum_from_cell(&Data::String("hp".to_owned())).datum_type,
+            Type::Text
+        );
+    }
+}